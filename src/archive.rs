@@ -0,0 +1,185 @@
+use crate::history;
+use crate::memo;
+use crate::path;
+use anyhow::{Context, Result};
+use chrono::NaiveDateTime;
+use fs_extra::dir::CopyOptions;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// `path_archive`が無い場合に、メモのディレクトリの中に作るアーカイブのディレクトリ
+pub const DIR_ARCHIVE: &str = "archive";
+
+/// メモのディレクトリの中で、ゴミ箱にしたメモを置くディレクトリ
+pub const DIR_TRASH: &str = ".menma/trash";
+
+const FORMAT_TRASH: &str = "%Y%m%d-%H%M%S";
+
+/// メモのアーカイブ先。`path_archive`が無い場合はメモのディレクトリの`archive`
+pub fn get_archive_dir(path_archive: Option<&String>, path: &Path) -> PathBuf {
+    match path_archive {
+        Some(dir) => PathBuf::from(dir),
+        None => path.parent().unwrap_or_else(|| Path::new("")).join(DIR_ARCHIVE),
+    }
+}
+
+/// 全てのアーカイブのディレクトリ
+pub fn get_archive_dirs(setting: &memo::Setting) -> Vec<String> {
+    match setting.get_archive_path() {
+        Some(dir) => vec![dir.clone()],
+        None => setting
+            .get_memo_path()
+            .iter()
+            .map(|dir| Path::new(dir).join(DIR_ARCHIVE).to_string_lossy().into_owned())
+            .collect(),
+    }
+}
+
+fn move_file_or_dir(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("can't create {}", parent.display()))?;
+    }
+    // 別のドライブへはrenameできないので、コピーしてから消す
+    if fs::rename(from, to).is_err() {
+        if from.is_dir() {
+            let mut options = CopyOptions::new();
+            options.content_only = true;
+            fs::create_dir_all(to)?;
+            fs_extra::dir::copy(from, to, &options).with_context(|| format!("can't copy {}", from.display()))?;
+            fs::remove_dir_all(from)?;
+        } else {
+            fs::copy(from, to).with_context(|| format!("can't copy {}", from.display()))?;
+            fs::remove_file(from)?;
+        }
+    }
+    Ok(())
+}
+
+/// メモを移動します。添付ファイルのディレクトリ(`assets/<メモ>`)とスナップショット(`.menma/history/<メモ>`)があれば一緒に移動します。
+pub fn move_memo(from: &Path, to: &Path) -> Result<()> {
+    move_file_or_dir(from, to).with_context(|| format!("can't move {} to {}", from.display(), to.display()))?;
+    let dir_assets = memo::get_assets_dir(from);
+    if dir_assets.is_dir() {
+        move_file_or_dir(&dir_assets, &memo::get_assets_dir(to))?;
+    }
+    let dir_history = history::get_history_dir(from);
+    if dir_history.is_dir() {
+        move_file_or_dir(&dir_history, &history::get_history_dir(to))?;
+    }
+    Ok(())
+}
+
+/// メモをアーカイブのディレクトリへ移動し、移動先を返します。
+pub fn archive_memo(path: &Path, path_archive: Option<&String>) -> Result<PathBuf> {
+    let name = path.file_name().unwrap_or_default();
+    let dest = path::get_unique_path(get_archive_dir(path_archive, path).join(name));
+    move_memo(path, &dest)?;
+    Ok(dest)
+}
+
+/// ゴミ箱の中のメモ
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrashItem {
+    path: PathBuf,
+    /// ゴミ箱に入れる前のファイル名
+    name: String,
+    time: NaiveDateTime,
+}
+
+impl TrashItem {
+    pub fn get_path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_time(&self) -> NaiveDateTime {
+        self.time
+    }
+
+    /// ゴミ箱に入れる前のパス
+    pub fn get_original_path(&self) -> PathBuf {
+        // `<メモのディレクトリ>/.menma/trash/<ファイル>`
+        let dir_memo = self.path.ancestors().nth(3).unwrap_or_else(|| Path::new(""));
+        dir_memo.join(&self.name)
+    }
+}
+
+/// ゴミ箱のファイル名`yyyymmdd-HHMMSS_<元のファイル名>`を、入れた時刻と元のファイル名に分けます。
+fn split_trash_name(name: &str) -> Option<(NaiveDateTime, &str)> {
+    let time = NaiveDateTime::parse_from_str(name.get(..15)?, FORMAT_TRASH).ok()?;
+    let name = name[15..].strip_prefix('_')?;
+    Some((time, name)).filter(|(_, name)| !name.is_empty())
+}
+
+/// メモをメモのディレクトリの`.menma/trash`へ移動し、移動先を返します。
+pub fn trash_memo(path: &Path, now: NaiveDateTime) -> Result<PathBuf> {
+    let name = path.file_name().map(|x| x.to_string_lossy().into_owned()).unwrap_or_default();
+    let dir_trash = path.parent().unwrap_or_else(|| Path::new("")).join(DIR_TRASH);
+    let dest = path::get_unique_path(dir_trash.join(format!("{}_{}", now.format(FORMAT_TRASH), name)));
+    move_memo(path, &dest)?;
+    Ok(dest)
+}
+
+/// メモのディレクトリのゴミ箱の中身を、新しい順に返します。
+pub fn list_trash(lst_dir: &[String]) -> Vec<TrashItem> {
+    let mut items: Vec<TrashItem> = lst_dir
+        .iter()
+        .filter_map(|dir| fs::read_dir(Path::new(dir).join(DIR_TRASH)).ok())
+        .flatten()
+        .filter_map(|file| {
+            let path = file.ok()?.path();
+            if !path.is_file() {
+                return None;
+            }
+            let file_name = path.file_name()?.to_string_lossy().into_owned();
+            let (time, name) = split_trash_name(&file_name)?;
+            Some(TrashItem { name: name.to_string(), time, path })
+        })
+        .collect();
+    items.sort_by_key(|item| std::cmp::Reverse(item.time));
+    items
+}
+
+/// ゴミ箱のメモを元の場所へ戻し、戻したパスを返します。
+pub fn restore_from_trash(item: &TrashItem) -> Result<PathBuf> {
+    let dest = path::get_unique_path(item.get_original_path());
+    move_memo(&item.path, &dest)?;
+    Ok(dest)
+}
+
+/// ゴミ箱のメモを消します。
+pub fn delete_from_trash(item: &TrashItem) -> Result<()> {
+    fs::remove_file(&item.path).with_context(|| format!("can't remove {}", item.path.display()))?;
+    let dir_assets = memo::get_assets_dir(&item.path);
+    if dir_assets.is_dir() {
+        fs::remove_dir_all(&dir_assets)?;
+    }
+    let dir_history = history::get_history_dir(&item.path);
+    if dir_history.is_dir() {
+        fs::remove_dir_all(&dir_history)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn trash_item_test() {
+        let (time, name) = split_trash_name("20211003-120005_211001_meeting.md").unwrap();
+        assert_eq!(time.format("%Y-%m-%d %H:%M:%S").to_string(), "2021-10-03 12:00:05");
+        assert_eq!(name, "211001_meeting.md");
+        assert!(split_trash_name("211001_meeting.md").is_none());
+
+        let item = TrashItem {
+            path: PathBuf::from("E:/memo/.menma/trash/20211003-120005_211001_meeting.md"),
+            name: name.to_string(),
+            time,
+        };
+        assert_eq!(item.get_original_path(), PathBuf::from("E:/memo/211001_meeting.md"));
+        assert_eq!(get_archive_dir(None, Path::new("E:/memo/a.md")), PathBuf::from("E:/memo/archive"));
+    }
+}
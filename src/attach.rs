@@ -0,0 +1,82 @@
+use crate::history;
+use crate::memo;
+use crate::path;
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 添付ファイルを置くディレクトリ。`is_shared`ならメモのディレクトリの`assets`、
+/// そうでなければメモごとの`assets/<メモのファイル名>`
+pub fn get_attach_dir(path: &Path, is_shared: bool) -> PathBuf {
+    if is_shared {
+        path.parent().unwrap_or_else(|| Path::new("")).join(memo::DIR_ASSETS)
+    } else {
+        memo::get_assets_dir(path)
+    }
+}
+
+/// メモから添付ファイルへのリンク。画像は`![]()`で埋め込みます。
+pub fn create_link(path: &Path, dest: &Path) -> String {
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let name = dest.file_name().map(|x| x.to_string_lossy().into_owned()).unwrap_or_default();
+    let url: Vec<String> = dest
+        .strip_prefix(dir)
+        .unwrap_or(dest)
+        .components()
+        .map(|x| path::encode_link(&x.as_os_str().to_string_lossy()))
+        .collect();
+    format!("{}[{}]({})", if path::is_image(&name) { "!" } else { "" }, name, url.join("/"))
+}
+
+/// 本文の最後にリンクを1行ずつ追加します。本文の改行コードに合わせます。
+pub fn append_links(text: &str, links: &[String]) -> String {
+    let newline = if text.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut text_new = text.to_string();
+    if !text_new.is_empty() && !text_new.ends_with('\n') {
+        text_new += newline;
+    }
+    for link in links {
+        text_new += link;
+        text_new += newline;
+    }
+    text_new
+}
+
+/// ファイルをメモの添付ファイルのディレクトリにコピーし、メモの最後にリンクを追加します。
+/// 同じ名前のファイルが既にあれば`_2`のように番号を付けます。コピーしたパスを返します。
+pub fn attach_files(path: &Path, files: &[PathBuf], is_shared: bool, retention: history::Retention) -> Result<Vec<PathBuf>> {
+    if let Some(file) = files.iter().find(|file| !file.is_file()) {
+        bail!("{} isn't a file", file.display());
+    }
+    let (text, encoding) = memo::read_text_with_encoding(path).with_context(|| format!("can't read {}", path.display()))?;
+    let dir = get_attach_dir(path, is_shared);
+    fs::create_dir_all(&dir).with_context(|| format!("can't create {}", dir.display()))?;
+
+    let options = fs_extra::file::CopyOptions::new();
+    let mut lst_dest: Vec<PathBuf> = Vec::new();
+    for file in files {
+        let name = path::sanitize_file_name(&file.file_name().unwrap_or_default().to_string_lossy());
+        let dest = path::get_unique_path(dir.join(name));
+        fs_extra::file::copy(file, &dest, &options).with_context(|| format!("can't copy {}", file.display()))?;
+        lst_dest.push(dest);
+    }
+
+    let links: Vec<String> = lst_dest.iter().map(|dest| create_link(path, dest)).collect();
+    history::save_snapshot(path, chrono::Local::now().naive_local(), retention)?;
+    memo::write_text(path, &append_links(&text, &links), encoding).with_context(|| format!("can't write {}", path.display()))?;
+    Ok(lst_dest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn create_link_test() {
+        let path = Path::new("memo/211003_design.md");
+        let dest = get_attach_dir(path, false).join("floor plan.png");
+        assert_eq!(create_link(path, &dest), "![floor plan.png](assets/211003_design/floor%20plan.png)");
+        let dest = get_attach_dir(path, true).join("spec.pdf");
+        assert_eq!(create_link(path, &dest), "[spec.pdf](assets/spec.pdf)");
+        assert_eq!(append_links("a\r\nb", &["[x](x)".to_string()]), "a\r\nb\r\n[x](x)\r\n");
+    }
+}
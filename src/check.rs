@@ -0,0 +1,151 @@
+use crate::link;
+use crate::markup::{self, Item, Syntax};
+use crate::memo;
+use std::fmt;
+use std::path::Path;
+
+/// 壊れたリンクの種類
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BrokenKind {
+    /// `[text](path)`や`![alt](path)`の先のファイルが無い
+    MissingFile,
+    /// `[[name]]`に一致するメモが無い
+    UnresolvedMemo,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrokenLink {
+    pub path: String,
+    /// 1から始まる行番号
+    pub line: usize,
+    pub target: String,
+    pub kind: BrokenKind,
+}
+
+impl fmt::Display for BrokenLink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            BrokenKind::MissingFile => write!(f, "{}:{}: missing file {}", self.path, self.line, self.target),
+            BrokenKind::UnresolvedMemo => write!(f, "{}:{}: unresolved memo [[{}]]", self.path, self.line, self.target),
+        }
+    }
+}
+
+/// `http://`や`mailto:`のような外部へのリンクか
+pub fn is_external_url(url: &str) -> bool {
+    if url.starts_with('#') || url.starts_with("mailto:") || url.contains("://") {
+        return true;
+    }
+    // `C:/...`のようなドライブ名はローカルのパスとして扱う
+    match url.find(':') {
+        Some(colon) => colon > 1 && url[..colon].chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c)),
+        None => false,
+    }
+}
+
+/// リンクの`#見出し`、`?query`を除き、`%20`のような文字をデコードします。
+pub fn to_local_path(url: &str) -> String {
+    let url = &url[..url.find(&['#', '?'][..]).unwrap_or(url.len())];
+    let bytes = url.as_bytes();
+    let mut decoded: Vec<u8> = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(byte) = hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// `sub`が`text`の中のどの行にあるかを返します。`sub`がパーサーの返した`text`の一部でない場合
+/// (CommonMarkの`&amp;`のようにデコードした文字列)は、`text`の中で最初に見つかった行を返します。
+fn line_of(text: &str, sub: &str) -> usize {
+    let start = text.as_ptr() as usize;
+    let ptr = sub.as_ptr() as usize;
+    let offset = if ptr >= start && ptr + sub.len() <= start + text.len() {
+        ptr - start
+    } else {
+        text.find(sub).unwrap_or(0)
+    };
+    text[..offset].matches('\n').count() + 1
+}
+
+/// `syntax`で書かれたメモの本文のリンクを確かめ、壊れたリンクを返します。
+pub fn check_memo_text(path: &str, text: &str, syntax: Syntax, lst_memo: &[memo::Memo]) -> Vec<BrokenLink> {
+    let dir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+    let mut broken: Vec<BrokenLink> = Vec::new();
+    for item in markup::items_with_table_cells(text, syntax) {
+        let (target, kind) = match item {
+            Item::Hyperlink(_, _, url) | Item::Image(_, url) if !url.is_empty() && !is_external_url(&url) => {
+                let target = to_local_path(&url);
+                if dir.join(&target).exists() {
+                    continue;
+                }
+                (url, BrokenKind::MissingFile)
+            }
+            Item::WikiLink(_, _, target) => {
+                // `[[image.png]]`のようにメモ以外のファイルを指す場合もある
+                if link::resolve_wiki_link(&target, lst_memo).is_some() || dir.join(&*target).is_file() {
+                    continue;
+                }
+                (target, BrokenKind::UnresolvedMemo)
+            }
+            _ => continue,
+        };
+        broken.push(BrokenLink {
+            path: path.to_string(),
+            line: line_of(text, &target),
+            target: target.to_string(),
+            kind,
+        });
+    }
+    broken
+}
+
+/// 全てのメモのリンクを確かめます。`common_mark_dirs`の中のメモはCommonMarkとして読みます。
+pub fn check_memos(lst_memo: &[memo::Memo], common_mark_dirs: &[String]) -> Vec<BrokenLink> {
+    lst_memo
+        .iter()
+        .filter_map(|memo| {
+            let text = memo::read_text(Path::new(memo.get_path())).ok()?;
+            let syntax = memo::get_syntax(memo.get_path(), common_mark_dirs);
+            Some(check_memo_text(memo.get_path(), &text, syntax, lst_memo))
+        })
+        .flatten()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn check_memo_text_test() {
+        let lst_memo = vec![memo::Memo::new("no_such_dir/211001_meeting.md".to_string(), vec![])];
+        let text = "# links\n[web](https://example.com) [[meeting]]\n\n```\n[[in code]]\n```\n![img](assets/none.png) [[nobody]]\n";
+        let broken = check_memo_text("no_such_dir/a.md", text, Syntax::EasyMark, &lst_memo);
+        assert_eq!(
+            broken.iter().map(|x| (x.line, x.target.as_str(), x.kind)).collect::<Vec<_>>(),
+            vec![(7, "assets/none.png", BrokenKind::MissingFile), (7, "nobody", BrokenKind::UnresolvedMemo)]
+        );
+
+        let text = "see [the spec][spec]\n\n[spec]: missing.md\n";
+        let broken = check_memo_text("no_such_dir/a.md", text, Syntax::CommonMark, &lst_memo);
+        assert_eq!(broken.iter().map(|x| (x.line, x.target.as_str())).collect::<Vec<_>>(), vec![(3, "missing.md")]);
+    }
+
+    #[test]
+    fn to_local_path_test() {
+        assert_eq!(to_local_path("my%20file.md#top"), "my file.md");
+        assert!(is_external_url("https://example.com"));
+        assert!(is_external_url("#heading"));
+        assert!(!is_external_url("C:/memo/a.md"));
+        assert!(!is_external_url("assets/a.png"));
+    }
+}
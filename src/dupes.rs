@@ -0,0 +1,201 @@
+use crate::memo;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+
+/// 何語(トークン)ずつまとめて比べるか
+const SHINGLE_SIZE: usize = 3;
+
+/// 重複しているメモのまとまり。最初のメモを残し、残りを重複とみなします。
+#[derive(Debug, Clone, PartialEq)]
+pub struct DupeGroup {
+    /// 全てのメモの本文が同じか
+    pub is_exact: bool,
+    /// (メモのパス, 最初のメモとの類似度)
+    pub memos: Vec<(String, f64)>,
+}
+
+/// 漢字、ひらがな、カタカナ、ハングルのように、単語を空白で区切らない文字か
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3040..=0x30ff // ひらがな、カタカナ
+        | 0x3400..=0x4dbf
+        | 0x4e00..=0x9fff // 漢字
+        | 0xf900..=0xfaff
+        | 0xac00..=0xd7af // ハングル
+        | 0xff66..=0xff9f) // 半角カタカナ
+}
+
+/// 比べるための単語の並びにします。英数字は小文字の単語に、CJKの文字は1文字ずつに分け、記号は除きます。
+pub fn normalize_tokens(text: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = Vec::new();
+    let mut word = String::new();
+    for c in text.chars() {
+        if is_cjk(c) {
+            if !word.is_empty() {
+                tokens.push(std::mem::take(&mut word));
+            }
+            tokens.push(c.to_string());
+        } else if c.is_alphanumeric() {
+            word.extend(c.to_lowercase());
+        } else if !word.is_empty() {
+            tokens.push(std::mem::take(&mut word));
+        }
+    }
+    if !word.is_empty() {
+        tokens.push(word);
+    }
+    tokens
+}
+
+/// 連続する`SHINGLE_SIZE`個の単語をまとめたものの集合。単語が少ない場合は全体を1つにします。
+pub fn create_shingles(tokens: &[String]) -> HashSet<u64> {
+    let hash = |tokens: &[String]| {
+        let mut hasher = DefaultHasher::new();
+        tokens.hash(&mut hasher);
+        hasher.finish()
+    };
+    if tokens.len() <= SHINGLE_SIZE {
+        return [hash(tokens)].iter().cloned().collect();
+    }
+    tokens.windows(SHINGLE_SIZE).map(hash).collect()
+}
+
+/// 2つの集合のJaccard係数
+pub fn jaccard(a: &HashSet<u64>, b: &HashSet<u64>) -> f64 {
+    let n_union = a.union(b).count();
+    if n_union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / n_union as f64
+}
+
+fn find_root(parents: &mut [usize], i: usize) -> usize {
+    let mut root = i;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    parents[i] = root;
+    root
+}
+
+/// `(パス, 本文)`の並びから重複をまとめます。各まとまりでは並びの早いメモを残すメモにします。
+/// 本文が同じメモと、類似度が`threshold`以上のメモをまとめます。空のメモは比べません。
+pub fn find_dupes(lst_text: &[(String, String)], threshold: f64) -> Vec<DupeGroup> {
+    let bodies: Vec<String> = lst_text.iter().map(|(_, text)| text.replace("\r\n", "\n").trim().to_string()).collect();
+    let mut parents: Vec<usize> = (0..lst_text.len()).collect();
+
+    // 本文が同じメモ
+    let mut first_of_body: HashMap<&str, usize> = HashMap::new();
+    for (i, body) in bodies.iter().enumerate().filter(|(_, body)| !body.is_empty()) {
+        match first_of_body.get(body.as_str()) {
+            Some(first) => parents[i] = *first,
+            None => {
+                first_of_body.insert(body, i);
+            }
+        }
+    }
+
+    // 似ているメモ(本文が同じものは最初の1つだけ比べる)
+    let mut representatives: Vec<usize> = first_of_body.values().cloned().collect();
+    representatives.sort_unstable();
+    let shingles: HashMap<usize, HashSet<u64>> = representatives
+        .iter()
+        .map(|i| (*i, create_shingles(&normalize_tokens(&bodies[*i]))))
+        .collect();
+    for (n, i) in representatives.iter().enumerate() {
+        for j in &representatives[n + 1..] {
+            if jaccard(&shingles[i], &shingles[j]) >= threshold {
+                let (root_i, root_j) = (find_root(&mut parents, *i), find_root(&mut parents, *j));
+                parents[root_i.max(root_j)] = root_i.min(root_j);
+            }
+        }
+    }
+
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut group_of_root: HashMap<usize, usize> = HashMap::new();
+    for (i, _) in bodies.iter().enumerate().filter(|(_, body)| !body.is_empty()) {
+        let root = find_root(&mut parents, i);
+        match group_of_root.get(&root) {
+            Some(group) => groups[*group].push(i),
+            None => {
+                group_of_root.insert(root, groups.len());
+                groups.push(vec![i]);
+            }
+        }
+    }
+
+    groups
+        .into_iter()
+        .filter(|group| group.len() > 1)
+        .map(|group| {
+            let first = group[0];
+            let shingles_first = create_shingles(&normalize_tokens(&bodies[first]));
+            let memos = group
+                .iter()
+                .map(|i| {
+                    let score = if bodies[*i] == bodies[first] {
+                        1.0
+                    } else {
+                        jaccard(&shingles_first, &create_shingles(&normalize_tokens(&bodies[*i])))
+                    };
+                    (lst_text[*i].0.clone(), score)
+                })
+                .collect();
+            DupeGroup {
+                is_exact: group.iter().all(|i| bodies[*i] == bodies[first]),
+                memos,
+            }
+        })
+        .collect()
+}
+
+/// メモの重複をまとめます。古いメモ(`yymmdd_`が早いメモ)を残すメモにします。タグのヘッダーは比べません。
+pub fn find_dupes_in_memos(lst_memo: &[memo::Memo], threshold: f64) -> Vec<DupeGroup> {
+    let mut lst_memo: Vec<&memo::Memo> = lst_memo.iter().collect();
+    lst_memo.sort_by_key(|memo| (memo.get_date_created().is_none(), memo.get_date_created(), memo.get_path().clone()));
+    let lst_text: Vec<(String, String)> = lst_memo
+        .iter()
+        .filter_map(|memo| {
+            let text = memo::read_text(Path::new(memo.get_path())).ok()?;
+            Some((memo.get_path().clone(), memo::strip_tags_header(&text).to_string()))
+        })
+        .collect();
+    find_dupes(&lst_text, threshold)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn normalize_tokens_test() {
+        assert_eq!(normalize_tokens("Hello, World! 会議メモ 2021"), vec!["hello", "world", "会", "議", "メ", "モ", "2021"]);
+    }
+
+    #[test]
+    fn find_dupes_test() {
+        let lst_text: Vec<(String, String)> = [
+            ("a.md", "今日の会議では来期の予算について話し合った。結論は来週に持ち越し。"),
+            ("b.md", "today we talked about the budget"),
+            ("c.md", "今日の会議では来期の予算について話し合った。結論は来週に持ち越し。\r\n"),
+            ("d.md", "今日の会議では来期の予算について話し合った。結論は来週に持ち越し!"),
+            ("e.md", "今日の会議では来期の予算について話し合った。結論は再来週に持ち越し。"),
+            ("f.md", ""),
+            ("g.md", ""),
+        ]
+        .iter()
+        .map(|(path, text)| (path.to_string(), text.to_string()))
+        .collect();
+        let groups = find_dupes(&lst_text, 0.8);
+        assert_eq!(groups.len(), 1);
+        assert!(!groups[0].is_exact);
+        let paths: Vec<&str> = groups[0].memos.iter().map(|(path, _)| path.as_str()).collect();
+        assert_eq!(paths, vec!["a.md", "c.md", "d.md", "e.md"]);
+        assert_eq!(groups[0].memos[1].1, 1.0);
+        assert!(groups[0].memos[3].1 >= 0.8 && groups[0].memos[3].1 < 1.0);
+
+        let groups = find_dupes(&lst_text[..3], 0.8);
+        assert!(groups[0].is_exact);
+    }
+}
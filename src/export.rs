@@ -0,0 +1,467 @@
+use crate::check;
+use crate::link;
+use crate::markup::{self, ColumnAlign, Item, Style, Syntax, Table};
+use crate::memo;
+use crate::path;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+const STYLE_SHEET: &str = "body { max-width: 50em; margin: 2em auto; padding: 0 1em; font-family: sans-serif; line-height: 1.6; }
+pre { background: #f4f4f4; padding: 0.5em; overflow-x: auto; }
+blockquote { border-left: 3px solid #ccc; margin: 0; padding-left: 1em; color: #555; }
+.tags a { margin-right: 0.5em; }
+.broken { color: #c00; }
+img { max-width: 100%; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccc; padding: 0.2em 0.6em; }
+";
+
+/// `../`や絶対パスで参照している、出力先の外のファイルをコピーするディレクトリ
+const DIR_OUTSIDE_FILES: &str = "files";
+
+pub fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{}</style>\n</head>\n<body>\n<nav><a href=\"index.html\">index</a></nav>\n{}</body>\n</html>\n",
+        escape_html(title),
+        STYLE_SHEET,
+        body
+    )
+}
+
+/// タグのページのファイル名。階層タグの`/`のようなファイル名に使えない文字は`_`にします。
+pub fn get_tag_file_name(tag: &str) -> String {
+    let name: String = tag
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    format!("tag_{}.html", name)
+}
+
+/// `name`が使われていれば、`name_2.html`のように番号を付けたファイル名を返します。
+fn get_unique_file_name(name: &str, is_used: impl Fn(&str) -> bool) -> String {
+    let stem = name.strip_suffix(".html").unwrap_or(name);
+    let mut name = name.to_string();
+    let mut n = 2;
+    while is_used(&name) {
+        name = format!("{}_{}.html", stem, n);
+        n += 1;
+    }
+    name
+}
+
+/// メモの全てのタグを名前順に返します。`work/rust`のような階層タグは親の`work`も含めます。
+fn collect_tags(lst_memo: &[memo::Memo]) -> Vec<String> {
+    let mut tags: Vec<String> = lst_memo
+        .iter()
+        .flat_map(|memo| memo.get_tags().iter().map(|x| x.trim()))
+        .filter(|x| !x.is_empty())
+        .flat_map(|tag| tag.match_indices('/').map(move |(i, _)| &tag[..i]).chain(std::iter::once(tag)))
+        .filter(|x| !x.is_empty())
+        .map(|x| x.to_string())
+        .collect();
+    tags.sort_unstable();
+    tags.dedup();
+    tags
+}
+
+/// タグからタグのページのファイル名を引く表を作ります。
+/// `work/rust`と`work_rust`のようにファイル名が重なる場合は`_2`のように番号を付けます。
+pub fn create_tag_file_names(tags: &[String]) -> HashMap<String, String> {
+    let mut files: HashMap<String, String> = HashMap::new();
+    for tag in tags {
+        let name = get_unique_file_name(&get_tag_file_name(tag), |name| {
+            name == "index.html" || files.values().any(|x| x == name)
+        });
+        files.insert(tag.clone(), name);
+    }
+    files
+}
+
+/// メモのパスから出力するHTMLのファイル名を引く表を作ります。
+/// `index.html`やタグのページ(`tag_files`)、他のメモとファイル名が重なる場合は`_2`のように番号を付けます。
+pub fn create_file_names(lst_memo: &[memo::Memo], tag_files: &HashMap<String, String>) -> HashMap<String, String> {
+    let mut files: HashMap<String, String> = HashMap::new();
+    for memo in lst_memo {
+        let stem = Path::new(memo.get_path())
+            .file_stem()
+            .map(|x| x.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let name = get_unique_file_name(&format!("{}.html", stem), |name| {
+            name == "index.html" || tag_files.values().any(|x| x == name) || files.values().any(|x| x == name)
+        });
+        files.insert(memo.get_path().clone(), name);
+    }
+    files
+}
+
+/// `../`や絶対パスで参照しているファイルの、出力先でのパス。出力先の中を指すパスなら`None`を返します。
+/// 別のディレクトリの同じ名前のファイルと重ならないよう、元のパスのハッシュを付けます。
+fn get_outside_file_path(dir: &Path, path: &str) -> Option<String> {
+    if Path::new(path).components().all(|x| matches!(x, Component::Normal(_))) {
+        return None;
+    }
+    let source = fs::canonicalize(dir.join(path)).ok().filter(|x| x.is_file())?;
+    let name = source.file_name()?.to_string_lossy().into_owned();
+    let hash = format!("{:x}", md5::compute(source.to_string_lossy().as_bytes()));
+    Some(format!("{}/{}_{}", DIR_OUTSIDE_FILES, &hash[..8], name))
+}
+
+fn styled_html(text: &str, style: &Style) -> String {
+    let mut html = escape_html(text);
+    let tags = [
+        (style.code, "code"),
+        (style.strong, "strong"),
+        (style.italics, "em"),
+        (style.underline, "u"),
+        (style.strikethrough, "s"),
+        (style.small, "small"),
+        (style.raised, "sup"),
+    ];
+    for (is_on, tag) in tags.iter() {
+        if *is_on {
+            html = format!("<{}>{}</{}>", tag, html, tag);
+        }
+    }
+    html
+}
+
+/// 書き出し中の1行
+#[derive(Default)]
+struct Line {
+    indent: usize,
+    is_quoted: bool,
+    /// 見出しのレベル(1から6)
+    heading: Option<u8>,
+    bullet: Option<String>,
+    html: String,
+}
+
+impl Line {
+    fn to_html(&self) -> String {
+        if self.html.trim().is_empty() && self.bullet.is_none() {
+            return String::new();
+        }
+        let html = match &self.bullet {
+            Some(bullet) => format!("{} {}", bullet, self.html),
+            None => self.html.clone(),
+        };
+        let html = if let Some(level) = self.heading {
+            format!("<h{}>{}</h{}>\n", level, html, level)
+        } else if self.indent > 0 {
+            format!("<p style=\"margin-left: {}em\">{}</p>\n", self.indent as f32 / 2.0, html)
+        } else {
+            format!("<p>{}</p>\n", html)
+        };
+        if self.is_quoted {
+            format!("<blockquote>{}</blockquote>\n", html)
+        } else {
+            html
+        }
+    }
+}
+
+/// 全てのメモをまとめてHTMLにします。
+pub struct Exporter<'a> {
+    lst_memo: &'a [memo::Memo],
+    files: HashMap<String, String>,
+    /// タグとタグのページのファイル名
+    tag_files: HashMap<String, String>,
+    backlinks: HashMap<String, Vec<String>>,
+    /// CommonMarkとして読むメモのディレクトリ
+    common_mark_dirs: Vec<String>,
+}
+
+impl<'a> Exporter<'a> {
+    pub fn new(lst_memo: &'a [memo::Memo]) -> Self {
+        let tag_files = create_tag_file_names(&collect_tags(lst_memo));
+        Exporter {
+            lst_memo,
+            files: create_file_names(lst_memo, &tag_files),
+            tag_files,
+            backlinks: link::create_backlink_index(lst_memo),
+            common_mark_dirs: Vec::new(),
+        }
+    }
+
+    pub fn with_common_mark_dirs(mut self, dirs: &[String]) -> Self {
+        self.common_mark_dirs = dirs.to_vec();
+        self
+    }
+
+    fn get_syntax(&self, path: &str) -> Syntax {
+        memo::get_syntax(path, &self.common_mark_dirs)
+    }
+
+    /// `[text](url)`の先がメモなら、そのメモのHTMLのファイル名を返します。
+    fn get_file_of_url(&self, dir: &Path, url: &str) -> Option<&String> {
+        let target = fs::canonicalize(dir.join(check::to_local_path(url))).ok()?;
+        let memo = self
+            .lst_memo
+            .iter()
+            .find(|memo| fs::canonicalize(memo.get_path()).is_ok_and(|path| path == target))?;
+        self.files.get(memo.get_path())
+    }
+
+    /// 添付ファイルへのリンク先。出力先の外のファイルはコピー先を指すよう書き換えます。
+    fn get_attachment_url(&self, dir: &Path, url: &str) -> String {
+        if check::is_external_url(url) {
+            return url.to_string();
+        }
+        match get_outside_file_path(dir, &check::to_local_path(url)) {
+            Some(file) => path::encode_link(&file),
+            None => url.to_string(),
+        }
+    }
+
+    /// メモの本文をHTMLにします。
+    pub fn render_memo_text(&self, path: &str, text: &str) -> String {
+        let dir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+        let mut html = String::new();
+        let mut line = Line::default();
+        for item in markup::parse(memo::strip_tags_header(text), self.get_syntax(path)) {
+            match item {
+                Item::Newline => {
+                    html += &line.to_html();
+                    line = Line::default();
+                }
+                Item::Text(style, _) | Item::Hyperlink(style, _, _) | Item::WikiLink(style, _, _) => {
+                    line.heading = line.heading.or(style.heading);
+                    line.html += &self.render_inline_item(dir, item);
+                }
+                Item::Image(..) => line.html += &self.render_inline_item(dir, item),
+                Item::Indentation(indent) => line.indent += indent,
+                Item::QuoteIndent => line.is_quoted = true,
+                Item::BulletPoint => line.bullet = Some("&bull;".to_string()),
+                Item::Task(is_done, _) => {
+                    let checked = if is_done { " checked" } else { "" };
+                    line.bullet = Some(format!("<input type=\"checkbox\" disabled{}>", checked));
+                }
+                Item::NumberedPoint(number) => line.bullet = Some(format!("{}.", number)),
+                Item::Separator => {
+                    html += &line.to_html();
+                    line = Line::default();
+                    html += "<hr>\n";
+                }
+                Item::CodeBlock(language, code) => {
+                    html += &line.to_html();
+                    line = Line::default();
+                    html += &format!(
+                        "<pre><code class=\"language-{}\">{}</code></pre>\n",
+                        escape_html(language.trim()),
+                        escape_html(code)
+                    );
+                }
+                Item::Table(table) => {
+                    html += &line.to_html();
+                    line = Line::default();
+                    html += &self.render_table(dir, &table);
+                }
+            }
+        }
+        html += &line.to_html();
+        html
+    }
+
+    /// 文字やリンクのように、行の中に書く項目をHTMLにします。
+    fn render_inline_item(&self, dir: &Path, item: Item) -> String {
+        match item {
+            Item::Text(style, text) => styled_html(&text, &style),
+            Item::Hyperlink(style, text, url) => {
+                let href = match self.get_file_of_url(dir, &url) {
+                    Some(file) => file.clone(),
+                    None => self.get_attachment_url(dir, &url),
+                };
+                format!("<a href=\"{}\">{}</a>", escape_html(&href), styled_html(&text, &style))
+            }
+            Item::Image(alt, url) => {
+                format!("<img src=\"{}\" alt=\"{}\">", escape_html(&self.get_attachment_url(dir, &url)), escape_html(&alt))
+            }
+            Item::WikiLink(style, label, target) => {
+                match link::resolve_wiki_link(&target, self.lst_memo).and_then(|memo| self.files.get(memo.get_path())) {
+                    Some(file) => format!("<a href=\"{}\">{}</a>", escape_html(file), styled_html(&label, &style)),
+                    None => format!("<span class=\"broken\">{}</span>", styled_html(&label, &style)),
+                }
+            }
+            _ => String::new(),
+        }
+    }
+
+    fn render_table(&self, dir: &Path, table: &Table) -> String {
+        let alignments = table.alignments();
+        let render_row = |cells: &[&str], tag: &str| -> String {
+            let mut html = String::from("<tr>");
+            for (cell, align) in cells.iter().zip(alignments.iter()) {
+                let style = match align {
+                    ColumnAlign::Left => " style=\"text-align: left\"",
+                    ColumnAlign::Center => " style=\"text-align: center\"",
+                    ColumnAlign::Right => " style=\"text-align: right\"",
+                    ColumnAlign::None => "",
+                };
+                let inner: String = table.parse_cell(cell).into_iter().map(|item| self.render_inline_item(dir, item)).collect();
+                html += &format!("<{}{}>{}</{}>", tag, style, inner, tag);
+            }
+            html + "</tr>\n"
+        };
+        let mut html = String::from("<table>\n<thead>\n");
+        html += &render_row(&table.header(), "th");
+        html += "</thead>\n<tbody>\n";
+        for row in table.rows() {
+            html += &render_row(&row, "td");
+        }
+        html + "</tbody>\n</table>\n"
+    }
+
+    fn render_memo_list(&self, lst_memo: &[&memo::Memo]) -> String {
+        let mut html = String::from("<ul>\n");
+        for memo in lst_memo {
+            let date = memo
+                .get_date_created()
+                .map(|x| x.format("%Y-%m-%d").to_string())
+                .unwrap_or_default();
+            html += &format!(
+                "<li>{} <a href=\"{}\">{}</a></li>\n",
+                date,
+                escape_html(&self.files[memo.get_path()]),
+                escape_html(&memo.get_title())
+            );
+        }
+        html += "</ul>\n";
+        html
+    }
+
+    fn render_memo_page(&self, memo: &memo::Memo, text: &str) -> String {
+        let mut body = format!("<h1>{}</h1>\n<p class=\"tags\">", escape_html(&memo.get_title()));
+        for tag in memo.get_tags().iter().map(|x| x.trim()).filter(|x| !x.is_empty()) {
+            body += &format!("<a href=\"{}\">#{}</a>", escape_html(&self.tag_files[tag]), escape_html(tag));
+        }
+        body += "</p>\n";
+        body += &self.render_memo_text(memo.get_path(), text);
+        if let Some(sources) = self.backlinks.get(memo.get_path()) {
+            let sources: Vec<&memo::Memo> = self
+                .lst_memo
+                .iter()
+                .filter(|memo| sources.contains(memo.get_path()))
+                .collect();
+            body += "<h2>Backlinks</h2>\n";
+            body += &self.render_memo_list(&sources);
+        }
+        page(&memo.get_title(), &body)
+    }
+
+    /// 本文から相対パスで参照しているファイルを`out_dir`の同じ相対パスへコピーします。
+    /// `../`や絶対パスのファイルは、出力先の外に書き出さないよう`out_dir/files`へコピーします。
+    fn copy_attachments(&self, path: &str, text: &str, out_dir: &Path) -> Result<()> {
+        let dir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+        for item in markup::items_with_table_cells(text, self.get_syntax(path)) {
+            let url = match item {
+                Item::Hyperlink(_, _, url) | Item::Image(_, url) if !check::is_external_url(&url) => check::to_local_path(&url),
+                _ => continue,
+            };
+            let source = dir.join(&url);
+            if !source.is_file() || self.get_file_of_url(dir, &url).is_some() {
+                continue;
+            }
+            let dest = match get_outside_file_path(dir, &url) {
+                Some(path) => out_dir.join(path),
+                None => out_dir.join(&url),
+            };
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::copy(&source, &dest).with_context(|| format!("can't copy {}", source.display()))?;
+        }
+        Ok(())
+    }
+
+    /// `out_dir`にメモごとのページ、タグごとの一覧、日付順の一覧(`index.html`)を書き出し、書き出したメモの数を返します。
+    pub fn export(&self, out_dir: &Path) -> Result<usize> {
+        fs::create_dir_all(out_dir).with_context(|| format!("can't create {}", out_dir.display()))?;
+        let mut count = 0;
+        for memo in self.lst_memo {
+            let text = match memo::read_text(Path::new(memo.get_path())) {
+                Ok(text) => text,
+                Err(_) => continue,
+            };
+            let dest: PathBuf = out_dir.join(&self.files[memo.get_path()]);
+            fs::write(&dest, self.render_memo_page(memo, &text)).with_context(|| format!("can't write {}", dest.display()))?;
+            self.copy_attachments(memo.get_path(), &text, out_dir)?;
+            count += 1;
+        }
+
+        // 新しい順
+        let mut lst_memo: Vec<&memo::Memo> = self.lst_memo.iter().collect();
+        lst_memo.sort_by_key(|memo| std::cmp::Reverse(memo.get_date_created()));
+
+        let tags = collect_tags(self.lst_memo);
+        for tag in &tags {
+            let lst_memo_tag: Vec<&memo::Memo> = lst_memo
+                .iter()
+                .filter(|memo| memo.get_tags().iter().any(|x| memo::is_match_tag(tag, x.trim())))
+                .cloned()
+                .collect();
+            let body = format!("<h1>#{}</h1>\n{}", escape_html(tag), self.render_memo_list(&lst_memo_tag));
+            fs::write(out_dir.join(&self.tag_files[tag]), page(&format!("#{}", tag), &body))?;
+        }
+
+        let mut body = String::from("<h1>Memos</h1>\n<p class=\"tags\">");
+        for tag in &tags {
+            body += &format!("<a href=\"{}\">#{}</a>", escape_html(&self.tag_files[tag]), escape_html(tag));
+        }
+        body += "</p>\n";
+        body += &self.render_memo_list(&lst_memo);
+        fs::write(out_dir.join("index.html"), page("Memos", &body))?;
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn render_memo_text_test() {
+        let lst_memo = vec![
+            memo::Memo::new("no_such_dir/211001_meeting.md".to_string(), vec![]),
+            memo::Memo::new("other_dir/211001_meeting.md".to_string(), vec![]),
+        ];
+        let exporter = Exporter::new(&lst_memo);
+        assert_eq!(exporter.files["other_dir/211001_meeting.md"], "211001_meeting_2.html");
+        let text = " <!---\n tags: #work \n --->\n# Title\nsee [[meeting]] and *a&b*\n- [[nobody]]\n";
+        assert_eq!(
+            exporter.render_memo_text("no_such_dir/a.md", text),
+            "<h1>Title</h1>\n<p>see <a href=\"211001_meeting.html\">meeting</a> and <strong>a&amp;b</strong></p>\n<p>&bull; <span class=\"broken\">nobody</span></p>\n"
+        );
+        assert_eq!(exporter.render_memo_text("no_such_dir/a.md", "### Sub\n####### no\n"), "<h3>Sub</h3>\n<p>####### no</p>\n");
+        assert_eq!(get_tag_file_name("work/rust"), "tag_work_rust.html");
+    }
+
+    #[test]
+    fn create_file_names_test() {
+        let lst_memo = vec![
+            memo::Memo::new("memo/tag_foo.md".to_string(), vec!["foo".to_string()]),
+            memo::Memo::new("memo/tag_bar.md".to_string(), vec![]),
+            memo::Memo::new("memo/index.md".to_string(), vec![]),
+        ];
+        let files = create_file_names(&lst_memo, &create_tag_file_names(&collect_tags(&lst_memo)));
+        assert_eq!(files["memo/tag_foo.md"], "tag_foo_2.html");
+        assert_eq!(files["memo/tag_bar.md"], "tag_bar.html");
+        assert_eq!(files["memo/index.md"], "index_2.html");
+
+        let tags = vec!["work.rust".to_string(), "work/rust".to_string(), "work_rust".to_string()];
+        let tag_files = create_tag_file_names(&tags);
+        assert_eq!(tag_files["work.rust"], "tag_work_rust.html");
+        assert_eq!(tag_files["work/rust"], "tag_work_rust_2.html");
+        assert_eq!(tag_files["work_rust"], "tag_work_rust_3.html");
+
+        let lst_memo = vec![memo::Memo::new("memo/a.md".to_string(), vec!["work/projectX/design".to_string()])];
+        assert_eq!(collect_tags(&lst_memo), vec!["work", "work/projectX", "work/projectX/design"]);
+    }
+}
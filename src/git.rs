@@ -0,0 +1,155 @@
+use crate::memo;
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// コミットしないもの(スナップショットとゴミ箱を置く`.menma`)
+const PATHSPEC: [&str; 2] = [".", ":(exclude).menma"];
+
+/// メモの1つの版
+#[derive(Debug, Clone, PartialEq)]
+pub struct Revision {
+    pub hash: String,
+    pub date: String,
+    pub subject: String,
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<Vec<u8>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .context("can't run git. Please install git")?;
+    if !output.status.success() {
+        bail!("git {} failed: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(output.stdout)
+}
+
+fn get_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+fn get_file_name(path: &Path) -> Result<String> {
+    match path.file_name() {
+        Some(name) => Ok(name.to_string_lossy().into_owned()),
+        None => bail!("{} isn't a file", path.display()),
+    }
+}
+
+/// `path`を含むメモのディレクトリ。どのメモのディレクトリにも無ければ`path`のあるディレクトリ
+pub fn get_memo_dir(dirs: &[String], path: &Path) -> PathBuf {
+    match dirs.iter().find(|dir| path.starts_with(dir)) {
+        Some(dir) => PathBuf::from(dir),
+        None => get_dir(path).to_path_buf(),
+    }
+}
+
+/// `dir`の変更を、`.menma`を除いて全てコミットします。変更が無ければ何もしません。
+/// 他のリポジトリにコミットしないよう、`dir`がリポジトリの一番上でなければエラーにします。
+pub fn commit_all(dir: &Path, message: &str) -> Result<()> {
+    let top = match git(dir, &["rev-parse", "--show-toplevel"]) {
+        Ok(top) => PathBuf::from(String::from_utf8_lossy(&top).trim()),
+        Err(_) => bail!("{} isn't a git repository. Please run `git init` there", dir.display()),
+    };
+    if fs::canonicalize(&top).ok() != fs::canonicalize(dir).ok() {
+        bail!(
+            "{} is inside the git repository {}. Please make a repository whose top level is the memo directory",
+            dir.display(),
+            top.display()
+        );
+    }
+    let mut args = vec!["add", "-A", "--"];
+    args.extend(&PATHSPEC);
+    git(dir, &args)?;
+    let mut args = vec!["status", "--porcelain", "--"];
+    args.extend(&PATHSPEC);
+    if git(dir, &args)?.is_empty() {
+        return Ok(());
+    }
+    // user.nameを設定していない環境でもコミットできるようにする
+    let mut args: Vec<&str> = Vec::new();
+    if git(dir, &["config", "user.name"]).is_err() {
+        args.extend(&["-c", "user.name=menma"]);
+    }
+    if git(dir, &["config", "user.email"]).is_err() {
+        args.extend(&["-c", "user.email=menma@localhost"]);
+    }
+    args.extend(&["commit", "-q", "-m", message, "--"]);
+    args.extend(&PATHSPEC);
+    git(dir, &args)?;
+    Ok(())
+}
+
+/// `use_git`が有効なら、`path`を含むメモのディレクトリの変更をコミットします。
+/// コミットできなくてもメモの操作は終わっているので、警告だけ出します。
+pub fn auto_commit(setting: &memo::Setting, path: &Path, message: &str) {
+    if !setting.is_git_enabled() {
+        return;
+    }
+    if let Err(e) = commit_all(&get_memo_dir(setting.get_memo_path(), path), message) {
+        eprintln!("warning: {:#}", e);
+    }
+}
+
+fn parse_log_line(line: &str) -> Option<Revision> {
+    let mut fields = line.splitn(3, '\t');
+    Some(Revision {
+        hash: fields.next()?.to_string(),
+        date: fields.next()?.to_string(),
+        subject: fields.next().unwrap_or("").to_string(),
+    })
+}
+
+/// メモの版を新しい順に返します。
+pub fn log(path: &Path) -> Result<Vec<Revision>> {
+    let name = get_file_name(path)?;
+    let args = [
+        "log",
+        "--follow",
+        "--format=%h%x09%ad%x09%s",
+        "--date=format:%Y-%m-%d %H:%M",
+        "--",
+        &name,
+    ];
+    let output = git(get_dir(path), &args)?;
+    Ok(String::from_utf8_lossy(&output).lines().filter_map(parse_log_line).collect())
+}
+
+/// `rev`の版のメモの本文を返します。
+pub fn show(path: &Path, rev: &str) -> Result<String> {
+    let spec = format!("{}:./{}", rev, get_file_name(path)?);
+    let bytes = git(get_dir(path), &["show", &spec])?;
+    Ok(memo::decode_text(bytes).0)
+}
+
+/// `memo@rev`をメモと版に分けます。
+pub fn split_revision(spec: &str) -> Result<(&str, &str)> {
+    match spec.rfind('@') {
+        Some(at) if at > 0 && at + 1 < spec.len() => Ok((&spec[..at], &spec[at + 1..])),
+        _ => bail!("please specify like <memo>@<rev>: {}", spec),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn parse_log_line_test() {
+        let revision = parse_log_line("1a2b3c4\t2021-10-03 12:00\tadd 211003_design.md").unwrap();
+        assert_eq!(
+            (revision.hash.as_str(), revision.date.as_str(), revision.subject.as_str()),
+            ("1a2b3c4", "2021-10-03 12:00", "add 211003_design.md")
+        );
+        assert_eq!(split_revision("design@HEAD~1").unwrap(), ("design", "HEAD~1"));
+        assert!(split_revision("design").is_err());
+        let dirs = vec!["E:/memo".to_string()];
+        assert_eq!(get_memo_dir(&dirs, Path::new("E:/memo/archive/a.md")), PathBuf::from("E:/memo"));
+        assert_eq!(get_memo_dir(&dirs, Path::new("E:/other/a.md")), PathBuf::from("E:/other"));
+    }
+}
@@ -0,0 +1,160 @@
+use crate::link;
+use crate::memo;
+use serde::Serialize;
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeKind {
+    Memo,
+    Tag,
+}
+
+/// グラフの頂点(メモ1つ、またはタグ1つ)
+#[derive(Debug, Serialize)]
+pub struct Node {
+    /// メモのパス。タグは`#タグ`
+    pub id: String,
+    pub title: String,
+    pub kind: NodeKind,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EdgeKind {
+    /// `[[link]]`によるリンク。`source`から`target`への向きがある
+    Link,
+    /// メモ(`source`)からそのメモのタグ(`target`)へ
+    Tag,
+}
+
+/// グラフの辺。`id`は`Node`の`id`
+#[derive(Debug, Serialize)]
+pub struct Edge {
+    pub source: String,
+    pub target: String,
+    pub kind: EdgeKind,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Graph {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+/// メモをつなぐグラフを作ります。リンク先が`lst_memo`に無いリンクは含めません。
+/// 同じタグを持つメモは、タグの頂点を通してつながります。
+pub fn create_graph(lst_memo: &[memo::Memo]) -> Graph {
+    let tags_of = |memo: &memo::Memo| -> Vec<String> {
+        memo.get_tags().iter().map(|x| x.trim().to_string()).filter(|x| !x.is_empty()).collect()
+    };
+    let mut nodes: Vec<Node> = lst_memo
+        .iter()
+        .map(|memo| Node {
+            id: memo.get_path().clone(),
+            title: memo.get_title(),
+            kind: NodeKind::Memo,
+            tags: tags_of(memo),
+        })
+        .collect();
+
+    let mut edges: Vec<Edge> = Vec::new();
+    let mut links: HashSet<(&str, &str)> = HashSet::new();
+    for memo in lst_memo {
+        for target in memo.get_links() {
+            let memo_target = match link::resolve_wiki_link(target, lst_memo) {
+                Some(memo_target) if memo_target.get_path() != memo.get_path() => memo_target,
+                _ => continue,
+            };
+            if links.insert((memo.get_path(), memo_target.get_path())) {
+                edges.push(Edge {
+                    source: memo.get_path().clone(),
+                    target: memo_target.get_path().clone(),
+                    kind: EdgeKind::Link,
+                });
+            }
+        }
+    }
+
+    // 最初に出てきた順
+    let mut tags: Vec<String> = Vec::new();
+    let mut tags_seen: HashSet<&str> = HashSet::new();
+    for node in &nodes {
+        for tag in &node.tags {
+            if tags_seen.insert(tag) {
+                tags.push(tag.clone());
+            }
+            edges.push(Edge {
+                source: node.id.clone(),
+                target: format!("#{}", tag),
+                kind: EdgeKind::Tag,
+            });
+        }
+    }
+    nodes.extend(tags.into_iter().map(|tag| Node {
+        id: format!("#{}", tag),
+        title: format!("#{}", tag),
+        kind: NodeKind::Tag,
+        tags: Vec::new(),
+    }));
+    Graph { nodes, edges }
+}
+
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Graphvizのdot形式にします。
+pub fn to_dot(graph: &Graph) -> String {
+    let mut dot = String::from("digraph menma {\n");
+    for node in &graph.nodes {
+        let shape = match node.kind {
+            NodeKind::Memo => "",
+            NodeKind::Tag => ", shape=plaintext",
+        };
+        dot += &format!("    \"{}\" [label=\"{}\"{}];\n", escape_dot(&node.id), escape_dot(&node.title), shape);
+    }
+    for edge in &graph.edges {
+        let attr = match edge.kind {
+            EdgeKind::Link => "",
+            EdgeKind::Tag => " [dir=none, style=dashed]",
+        };
+        dot += &format!("    \"{}\" -> \"{}\"{};\n", escape_dot(&edge.source), escape_dot(&edge.target), attr);
+    }
+    dot += "}\n";
+    dot
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn create_graph_test() {
+        let lst_memo = vec![
+            memo::Memo::new("211001_meeting.md".to_string(), vec!["work".to_string(), "rust".to_string()])
+                .with_links(vec!["design".to_string(), "design".to_string(), "unknown".to_string()]),
+            memo::Memo::new("211003_design.md".to_string(), vec!["rust".to_string()]),
+            memo::Memo::new("211005_diary.md".to_string(), vec!["life".to_string()]),
+        ];
+        let graph = create_graph(&lst_memo);
+        let nodes: Vec<(&str, NodeKind)> = graph.nodes.iter().map(|x| (x.id.as_str(), x.kind)).collect();
+        assert_eq!(nodes[3..], [("#work", NodeKind::Tag), ("#rust", NodeKind::Tag), ("#life", NodeKind::Tag)]);
+        let edges: Vec<(&str, &str, EdgeKind)> =
+            graph.edges.iter().map(|x| (x.source.as_str(), x.target.as_str(), x.kind)).collect();
+        assert_eq!(
+            edges,
+            vec![
+                ("211001_meeting.md", "211003_design.md", EdgeKind::Link),
+                ("211001_meeting.md", "#work", EdgeKind::Tag),
+                ("211001_meeting.md", "#rust", EdgeKind::Tag),
+                ("211003_design.md", "#rust", EdgeKind::Tag),
+                ("211005_diary.md", "#life", EdgeKind::Tag),
+            ]
+        );
+        let dot = to_dot(&graph);
+        assert!(dot.contains("\"#rust\" [label=\"#rust\", shape=plaintext];"));
+        assert!(dot.contains("\"211003_design.md\" -> \"#rust\" [dir=none, style=dashed];"));
+    }
+}
@@ -2,10 +2,18 @@ use eframe::{
     egui::{self, FontDefinitions, FontFamily, FontData, ScrollArea},
     epi,
 };
+use std::collections::HashMap;
 use std::fs::{read_to_string, read, File};
 use std::io::BufReader;
+use std::path::Path;
+use crate::archive;
+use crate::attach;
+use crate::git;
+use crate::history;
+use crate::link;
 use crate::memo;
-mod easy_mark;
+use crate::task;
+pub mod easy_mark;
 /// We derive Deserialize/Serialize so we can persist app state on shutdown.
 #[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
 #[cfg_attr(feature = "persistence", serde(default))] // if we add new fields, give them default values when deserializing old state
@@ -19,6 +27,23 @@ pub struct TemplateApp {
     search: String,
     lst_memo: Vec<memo::Memo>,
     path_of_show: String,
+    tag_tree: Vec<memo::TagNode>,
+    lst_memo_all: Vec<memo::Memo>,
+    backlinks: HashMap<String, Vec<String>>,
+    is_loaded_memo: bool,
+    /// `history_path`のメモの版(`use_git`が有効な場合)
+    history: Vec<git::Revision>,
+    /// `history`を読んだメモ。コミットした後は空にして、次の描画で読み直す
+    history_path: String,
+    /// プレビューに表示している古い版
+    rev_of_show: Option<String>,
+    /// ドロップしたファイルを添付した結果
+    attach_message: String,
+    /// チェックボックスを押してタスクを書き換えられなかった理由
+    task_message: String,
+    /// プレビューしているメモの画像
+    #[cfg_attr(feature = "persistence", serde(skip))]
+    images: easy_mark::ImageCache,
 }
 
 impl Default for TemplateApp {
@@ -30,6 +55,16 @@ impl Default for TemplateApp {
             search: "".to_owned(),
             lst_memo: Vec::new(),
             path_of_show: "".to_owned(),
+            tag_tree: Vec::new(),
+            lst_memo_all: Vec::new(),
+            backlinks: HashMap::new(),
+            is_loaded_memo: false,
+            history: Vec::new(),
+            history_path: "".to_owned(),
+            rev_of_show: None,
+            attach_message: "".to_owned(),
+            task_message: "".to_owned(),
+            images: easy_mark::ImageCache::default(),
         }
     }
 }
@@ -75,8 +110,8 @@ impl epi::App for TemplateApp {
 
     /// Called each time the UI needs repainting, which may be many times per second.
     /// Put your widgets into a `SidePanel`, `TopPanel`, `CentralPanel`, `Window` or `Area`.
-    fn update(&mut self, ctx: &egui::CtxRef, _frame: &epi::Frame) {
-        let Self { label: _, value: _ , search, lst_memo, path_of_show} = self;
+    fn update(&mut self, ctx: &egui::CtxRef, frame: &epi::Frame) {
+        let Self { label: _, value: _ , search, lst_memo, path_of_show, tag_tree, lst_memo_all, backlinks, is_loaded_memo, history, history_path, rev_of_show, attach_message, task_message, images} = self;
 
         // Examples of how to create different panels and windows.
         // Pick whichever suits you.
@@ -92,30 +127,70 @@ impl epi::App for TemplateApp {
             ui.horizontal(|ui| {
                 ui.label("search tags");
                 let response = ui.add(egui::TextEdit::singleline(&mut *search));
-                if response.lost_focus() && ui.input().key_pressed(egui::Key::Enter) {
-                    *lst_memo = memo::create_memo_list(setting.get_memo_path());
-                    let tag = vec![search.to_string()];
-                    *lst_memo =
-                        if tag.iter().any(|x| x.contains("all")) {
-                            lst_memo.clone()
-                        } else {
-                            lst_memo
-                                .iter()
-                                .filter(|memo| memo::is_include_these_tags(&tag, memo.get_tags()))
-                                .cloned()
-                                .collect()
-                    };
+                let is_search = response.lost_focus() && ui.input().key_pressed(egui::Key::Enter);
+                if is_search || !*is_loaded_memo {
+                    *lst_memo_all = memo::create_memo_list(setting.get_memo_path());
+                    *tag_tree = memo::create_tag_tree(lst_memo_all);
+                    *backlinks = link::create_backlink_index(lst_memo_all);
+                    *is_loaded_memo = true;
+                    if is_search {
+                        let tag = vec![search.to_string()];
+                        *lst_memo = memo::filter_memo_by_tags(lst_memo_all, &tag);
+                    }
                 }
             });
         });
 
         egui::SidePanel::left("MemoList").show(ctx, |ui| {
+            ui.heading("Tags");
+            let mut tag_clicked: Option<String> = None;
+            ScrollArea::vertical().id_source("TagTree").max_height(200.0).show(ui, |ui| {
+                for node in tag_tree.iter() {
+                    tag_tree_ui(ui, node, &mut tag_clicked);
+                }
+            });
+            if let Some(tag) = tag_clicked {
+                *search = tag;
+                *lst_memo = memo::filter_memo_by_tags(lst_memo_all, &vec![search.to_string()]);
+            }
+            ui.separator();
+
+            // ドロップしたファイルをプレビューしているメモに添付する
+            let files_dropped: Vec<std::path::PathBuf> = ctx.input().raw.dropped_files.iter().filter_map(|file| file.path.clone()).collect();
+            if !files_dropped.is_empty() {
+                *attach_message = if path_of_show.is_empty() {
+                    "select a memo before dropping files.".to_owned()
+                } else {
+                    let retention = history::Retention::from_setting(&setting);
+                    match attach::attach_files(Path::new(&*path_of_show), &files_dropped, false, retention) {
+                        Ok(lst_dest) => {
+                            git::auto_commit(&setting, Path::new(&*path_of_show), &format!("attach files to {}", path_of_show));
+                            history_path.clear();
+                            format!("attached {} files.", lst_dest.len())
+                        }
+                        Err(e) => format!("{:#}", e),
+                    }
+                };
+            }
+            let is_hovered = !ctx.input().raw.hovered_files.is_empty();
+            ui.group(|ui| {
+                let text = if path_of_show.is_empty() { "drop files here to attach" } else { "drop files here to attach to the memo" };
+                if is_hovered {
+                    ui.colored_label(egui::Color32::LIGHT_BLUE, text);
+                } else {
+                    ui.label(text);
+                }
+                if !attach_message.is_empty() {
+                    ui.label(&*attach_message);
+                }
+            });
+            ui.separator();
+
             ui.heading("MemoList");
 
             let mut selected_candidate: Vec<egui::Response>= Vec::new();
             let lst_memo_: &Vec<memo::Memo> = &lst_memo.clone();
-            for memo in lst_memo {
-                // TODO:ドラッグの実装
+            for memo in lst_memo.iter() {
                 let response = ui.add(egui::TextEdit::singleline(&mut memo.get_path().clone()));
                 selected_candidate.push(response);
             }
@@ -140,18 +215,113 @@ impl epi::App for TemplateApp {
         egui::CentralPanel::default().show(ctx, |ui| {
             // TODO:VsCodeで表示する機能
             ui.heading("Preview");
+            if !path_of_show.is_empty() {
+                // (結果, コミットのメッセージの動詞)
+                let mut result: Option<(anyhow::Result<std::path::PathBuf>, &str)> = None;
+                ui.horizontal(|ui| {
+                    if ui.button("archive").clicked() {
+                        result = Some((archive::archive_memo(Path::new(&*path_of_show), setting.get_archive_path()), "archive"));
+                    }
+                    if ui.button("move to trash").clicked() {
+                        result = Some((archive::trash_memo(Path::new(&*path_of_show), chrono::Local::now().naive_local()), "trash"));
+                    }
+                });
+                match result {
+                    Some((Ok(_), verb)) => {
+                        git::auto_commit(&setting, Path::new(&*path_of_show), &format!("{} {}", verb, path_of_show));
+                        history_path.clear();
+                        lst_memo.retain(|memo| memo.get_path() != path_of_show);
+                        *path_of_show = "".to_owned();
+                        *is_loaded_memo = false;
+                    }
+                    Some((Err(e), _)) => {
+                        ui.label(format!("{:#}", e));
+                    }
+                    None => {}
+                }
+            }
             // The central panel the region left after adding TopPanel's and SidePanel's
             ScrollArea::vertical().show(ui, |ui| {
                 if path_of_show != "" {
-                    let contents = match read_to_string(&*path_of_show) {
-                        Ok(content) => content,
-                        Err(_) => {
-                            let s = read(&*path_of_show).unwrap();
-                            let (res, _, _) = encoding_rs::SHIFT_JIS.decode(&s);
-                            res.into_owned()
-                        }
+                    if setting.is_git_enabled() && history_path != path_of_show {
+                        *history = git::log(Path::new(&*path_of_show)).unwrap_or_default();
+                        *history_path = path_of_show.clone();
+                        *rev_of_show = None;
+                    }
+                    let contents = match rev_of_show {
+                        Some(rev) => git::show(Path::new(&*path_of_show), rev).unwrap_or_else(|e| format!("{:#}", e)),
+                        None => match read_to_string(&*path_of_show) {
+                            Ok(content) => content,
+                            Err(_) => {
+                                let s = read(&*path_of_show).unwrap();
+                                let (res, _, _) = encoding_rs::SHIFT_JIS.decode(&s);
+                                res.into_owned()
+                            }
+                        },
                     };
-                    easy_mark::easy_mark(ui, &contents);
+                    // [[link]]がクリックされたらリンク先のメモを表示する
+                    let mut path_clicked: Option<String> = None;
+                    images.set_memo(frame, Path::new(&*path_of_show));
+                    match easy_mark::easy_mark(ui, &contents, setting.get_syntax(path_of_show), frame, images) {
+                        Some(easy_mark::Action::WikiLink(target)) => {
+                            if let Some(memo) = link::resolve_wiki_link(&target, lst_memo_all) {
+                                path_clicked = Some(memo.get_path().clone());
+                            }
+                        }
+                        // 古い版を表示している間はファイルを書き換えない
+                        Some(easy_mark::Action::ToggleTask(line)) if rev_of_show.is_none() => {
+                            let path = Path::new(&*path_of_show);
+                            let retention = history::Retention::from_setting(&setting);
+                            let result = history::save_snapshot(path, chrono::Local::now().naive_local(), retention)
+                                .and_then(|_| task::toggle_task_at(path, line, None));
+                            *task_message = match result {
+                                Ok(()) => {
+                                    git::auto_commit(&setting, path, &format!("toggle task in {}", path_of_show));
+                                    history_path.clear();
+                                    "".to_owned()
+                                }
+                                Err(e) => format!("{:#}", e),
+                            };
+                        }
+                        _ => {}
+                    }
+                    if !task_message.is_empty() {
+                        ui.label(&*task_message);
+                    }
+
+                    ui.separator();
+                    ui.heading("Backlinks");
+                    match backlinks.get(&*path_of_show) {
+                        Some(sources) => {
+                            for source in sources {
+                                if ui.selectable_label(false, source).clicked() {
+                                    path_clicked = Some(source.clone());
+                                }
+                            }
+                        }
+                        None => {
+                            ui.label("no memo links here.");
+                        }
+                    }
+
+                    if setting.is_git_enabled() {
+                        ui.separator();
+                        ui.heading("History");
+                        if rev_of_show.is_some() && ui.button("show current").clicked() {
+                            *rev_of_show = None;
+                        }
+                        for revision in history.iter() {
+                            let label = format!("{} {} {}", revision.date, revision.hash, revision.subject);
+                            let is_selected = rev_of_show.as_ref() == Some(&revision.hash);
+                            if ui.selectable_label(is_selected, label).clicked() {
+                                *rev_of_show = Some(revision.hash.clone());
+                            }
+                        }
+                    }
+
+                    if let Some(path) = path_clicked {
+                        *path_of_show = path;
+                    }
                 }
             });
             ui.add(egui::github_link_file!(
@@ -171,3 +341,24 @@ impl epi::App for TemplateApp {
         }
     }
 }
+
+/// 階層タグを折りたたみ可能なグループとして表示します。クリックされたタグを`tag_clicked`に入れます。
+fn tag_tree_ui(ui: &mut egui::Ui, node: &memo::TagNode, tag_clicked: &mut Option<String>) {
+    let label = format!("{} ({})", node.get_name(), node.get_count());
+    if node.get_children().is_empty() {
+        if ui.selectable_label(false, label).clicked() {
+            *tag_clicked = Some(node.get_path().clone());
+        }
+    } else {
+        egui::CollapsingHeader::new(label)
+            .id_source(node.get_path())
+            .show(ui, |ui| {
+                if ui.selectable_label(false, "(all)").clicked() {
+                    *tag_clicked = Some(node.get_path().clone());
+                }
+                for child in node.get_children() {
+                    tag_tree_ui(ui, child, tag_clicked);
+                }
+            });
+    }
+}
@@ -1,12 +1,75 @@
-use super::easy_mark_parser as easy_mark;
+use crate::markup as easy_mark;
+use crate::check;
 use eframe::egui::*;
+use eframe::epi;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// プレビューしているメモの画像のテクスチャ。メモが変わったら作り直します。
+#[derive(Default)]
+pub struct ImageCache {
+    path_memo: PathBuf,
+    /// 読み込めなかった画像は`None`にして、毎フレーム読み直さないようにする
+    textures: HashMap<PathBuf, Option<(TextureId, Vec2)>>,
+}
+
+impl ImageCache {
+    /// `path_memo`のメモの画像を表示する準備をします。別のメモの画像のテクスチャは解放します。
+    pub fn set_memo(&mut self, frame: &epi::Frame, path_memo: &Path) {
+        if self.path_memo == path_memo {
+            return;
+        }
+        for (id, _) in self.textures.values().flatten() {
+            frame.free_texture(*id);
+        }
+        self.textures.clear();
+        self.path_memo = path_memo.to_path_buf();
+    }
+
+    /// メモからの相対パスの画像をテクスチャにして、テクスチャと画像の大きさを返します。
+    fn load(&mut self, frame: &epi::Frame, url: &str) -> Option<(TextureId, Vec2)> {
+        if check::is_external_url(url) {
+            return None;
+        }
+        let dir = self.path_memo.parent().unwrap_or_else(|| Path::new(""));
+        let path = dir.join(check::to_local_path(url));
+        *self.textures.entry(path).or_insert_with_key(|path| {
+            let image = image::open(path).ok()?.to_rgba8();
+            let size = [image.width() as usize, image.height() as usize];
+            let id = frame.alloc_texture(epi::Image::from_rgba_unmultiplied(size, &image));
+            Some((id, vec2(size[0] as f32, size[1] as f32)))
+        })
+    }
+}
+
+/// プレビューでクリックされたもの
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    /// `[[wiki link]]`のリンク先
+    WikiLink(String),
+    /// チェックボックスを押したタスクの行番号(1から)
+    ToggleTask(usize),
+}
 
 /// Parse and display a VERY simple and small subset of Markdown.
-pub fn easy_mark(ui: &mut Ui, easy_mark: &str) {
-    easy_mark_it(ui, easy_mark::Parser::new(easy_mark));
+///
+/// Returns what was clicked in this frame, if any.
+pub fn easy_mark(
+    ui: &mut Ui,
+    easy_mark: &str,
+    syntax: easy_mark::Syntax,
+    frame: &epi::Frame,
+    images: &mut ImageCache,
+) -> Option<Action> {
+    easy_mark_it(ui, easy_mark::parse(easy_mark, syntax).into_iter(), frame, images)
 }
 
-pub fn easy_mark_it<'em>(ui: &mut Ui, items: impl Iterator<Item = easy_mark::Item<'em>>) {
+pub fn easy_mark_it<'em>(
+    ui: &mut Ui,
+    items: impl Iterator<Item = easy_mark::Item<'em>>,
+    frame: &epi::Frame,
+    images: &mut ImageCache,
+) -> Option<Action> {
     let initial_size = vec2(
         ui.available_width(),
         ui.spacing().interact_size.y, // Assume there will be
@@ -20,13 +83,22 @@ pub fn easy_mark_it<'em>(ui: &mut Ui, items: impl Iterator<Item = easy_mark::Ite
         ui.spacing_mut().item_spacing.x = 0.0;
         ui.set_row_height(ui.fonts()[TextStyle::Body].row_height());
 
+        let mut clicked = None;
         for item in items {
-            item_ui(ui, item);
+            clicked = item_ui(ui, item, frame, images).or(clicked.take());
         }
-    });
+        clicked
+    })
+    .inner
 }
 
-pub fn item_ui(ui: &mut Ui, item: easy_mark::Item<'_>) {
+/// Returns the target when a `[[wiki link]]` or a checkbox is clicked.
+pub fn item_ui<'em>(
+    ui: &mut Ui,
+    item: easy_mark::Item<'em>,
+    frame: &epi::Frame,
+    images: &mut ImageCache,
+) -> Option<Action> {
     let row_height = ui.fonts()[TextStyle::Body].row_height();
     let one_indent = row_height / 2.0;
 
@@ -39,12 +111,37 @@ pub fn item_ui(ui: &mut Ui, item: easy_mark::Item<'_>) {
         }
 
         easy_mark::Item::Text(style, text) => {
-            ui.label(rich_text_from_style(text, &style));
+            ui.label(rich_text_from_style(&text, &style));
         }
         easy_mark::Item::Hyperlink(style, text, url) => {
-            let label = rich_text_from_style(text, &style);
+            let label = rich_text_from_style(&text, &style);
             ui.add(Hyperlink::from_label_and_url(label, url));
         }
+        easy_mark::Item::Image(alt, url) => match images.load(frame, &url) {
+            Some((id, size)) => {
+                // パネルの幅より大きい画像は縮める
+                let width = ui.max_rect().width().max(1.0);
+                let size = if size.x > width { size * (width / size.x) } else { size };
+                ui.end_row();
+                ui.image(id, size).on_hover_text(alt.as_ref());
+                ui.end_row();
+            }
+            None if check::is_external_url(&url) => {
+                ui.add(Hyperlink::from_label_and_url(format!("[image: {}]", alt), url));
+            }
+            None => {
+                ui.label(RichText::new(format!("[image: {}]", alt)).weak()).on_hover_text(url.as_ref());
+            }
+        },
+        easy_mark::Item::WikiLink(style, label, target) => {
+            let label = rich_text_from_style(&label, &style)
+                .color(ui.visuals().hyperlink_color)
+                .underline();
+            let response = ui.add(Label::new(label).sense(Sense::click()));
+            if response.on_hover_text(target.as_ref()).clicked() {
+                return Some(Action::WikiLink(target.into_owned()));
+            }
+        }
 
         easy_mark::Item::Separator => {
             ui.add(Separator::default().horizontal());
@@ -68,6 +165,13 @@ pub fn item_ui(ui: &mut Ui, item: easy_mark::Item<'_>) {
             bullet_point(ui, one_indent);
             ui.allocate_exact_size(vec2(one_indent, row_height), Sense::hover());
         }
+        easy_mark::Item::Task(is_done, line) => {
+            let mut checked = is_done;
+            let response = ui.add_enabled(line.is_some(), Checkbox::new(&mut checked, ""));
+            if let (true, Some(line)) = (response.clicked(), line) {
+                return Some(Action::ToggleTask(line));
+            }
+        }
         easy_mark::Item::NumberedPoint(number) => {
             let width = 3.0 * one_indent;
             numbered_point(ui, width, number);
@@ -84,7 +188,69 @@ pub fn item_ui(ui: &mut Ui, item: easy_mark::Item<'_>) {
                 Shape::rect_filled(rect, 1.0, code_bg_color),
             );
         }
+        easy_mark::Item::Table(table) => {
+            let alignments = table.alignments();
+            let mut clicked = None;
+            ui.end_row();
+            // 同じメモに表が複数あってもIDが重ならないよう、表の中身をIDにする
+            let id = ui.id().with(table.cells());
+            Grid::new(id).striped(true).show(ui, |ui| {
+                let rows = std::iter::once(table.header()).chain(table.rows());
+                for (i, row) in rows.enumerate() {
+                    for (cell, align) in row.iter().zip(alignments.iter()) {
+                        let items = table.parse_cell(cell).into_iter().map(|item| match item {
+                            // 見出しの行は太字にする
+                            easy_mark::Item::Text(style, text) if i == 0 => {
+                                easy_mark::Item::Text(easy_mark::Style { strong: true, ..style }, text)
+                            }
+                            item => item,
+                        });
+                        clicked = cell_ui(ui, items, *align, frame, images).or(clicked.take());
+                    }
+                    ui.end_row();
+                }
+            });
+            ui.end_row();
+            return clicked;
+        }
     };
+    None
+}
+
+/// 表の1つのセル。右寄せの列は右から並べるので、項目を逆順に置きます。
+fn cell_ui<'em>(
+    ui: &mut Ui,
+    items: impl Iterator<Item = easy_mark::Item<'em>>,
+    align: easy_mark::ColumnAlign,
+    frame: &epi::Frame,
+    images: &mut ImageCache,
+) -> Option<Action> {
+    let mut items: Vec<easy_mark::Item<'em>> = items.collect();
+    let layout = match align {
+        easy_mark::ColumnAlign::Right => {
+            items.reverse();
+            Layout::right_to_left()
+        }
+        easy_mark::ColumnAlign::Center => Layout::top_down(Align::Center),
+        _ => Layout::left_to_right(),
+    };
+    ui.with_layout(layout, |ui| {
+        ui.spacing_mut().item_spacing.x = 0.0;
+        let mut clicked = None;
+        if align == easy_mark::ColumnAlign::Center {
+            ui.horizontal(|ui| {
+                for item in items {
+                    clicked = item_ui(ui, item, frame, images).or(clicked.take());
+                }
+            });
+        } else {
+            for item in items {
+                clicked = item_ui(ui, item, frame, images).or(clicked.take());
+            }
+        }
+        clicked
+    })
+    .inner
 }
 
 fn rich_text_from_style(text: &str, style: &easy_mark::Style) -> RichText {
@@ -101,6 +267,7 @@ fn rich_text_from_style(text: &str, style: &easy_mark::Style) -> RichText {
     } = *style;
 
     let small = small || raised; // Raised text is also smaller
+    let heading = heading.is_some();
 
     let mut rich_text = RichText::new(text);
     if heading && !small {
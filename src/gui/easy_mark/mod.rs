@@ -1,7 +1,5 @@
 //! Experimental markup language
 
-pub mod easy_mark_parser;
 mod easy_mark_viewer;
 
-pub use easy_mark_parser as parser;
-pub use easy_mark_viewer::easy_mark;
+pub use easy_mark_viewer::{easy_mark, Action, ImageCache};
@@ -0,0 +1,162 @@
+use crate::memo;
+use anyhow::{bail, Context, Result};
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// メモのディレクトリの中で、スナップショットを置くディレクトリ
+pub const DIR_HISTORY: &str = ".menma/history";
+
+const FORMAT_SNAPSHOT: &str = "%Y%m%d-%H%M%S";
+
+/// 書き換える前のメモの写し
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    path: PathBuf,
+    time: NaiveDateTime,
+}
+
+impl Snapshot {
+    pub fn get_path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    pub fn get_time(&self) -> NaiveDateTime {
+        self.time
+    }
+}
+
+/// スナップショットを残す数と日数
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Retention {
+    pub max_count: Option<usize>,
+    pub max_days: Option<i64>,
+}
+
+impl Retention {
+    pub fn from_setting(setting: &memo::Setting) -> Self {
+        Retention {
+            max_count: setting.get_history_max_count(),
+            max_days: setting.get_history_max_days(),
+        }
+    }
+}
+
+/// メモのスナップショットを置くディレクトリ(`.menma/history/<メモのファイル名>`)
+pub fn get_history_dir(path: &Path) -> PathBuf {
+    let name = path.file_name().map(|x| x.to_string_lossy().into_owned()).unwrap_or_default();
+    path.parent().unwrap_or_else(|| Path::new("")).join(DIR_HISTORY).join(name)
+}
+
+/// メモのスナップショットを新しい順に返します。
+pub fn list_snapshots(path: &Path) -> Vec<Snapshot> {
+    let files = match fs::read_dir(get_history_dir(path)) {
+        Ok(files) => files,
+        Err(_) => return Vec::new(),
+    };
+    let mut snapshots: Vec<Snapshot> = files
+        .filter_map(|file| {
+            let path = file.ok()?.path();
+            let name = path.file_name()?.to_string_lossy().into_owned();
+            let time = NaiveDateTime::parse_from_str(name.get(..15)?, FORMAT_SNAPSHOT).ok()?;
+            Some(Snapshot { path, time })
+        })
+        .collect();
+    snapshots.sort_by(|a, b| (b.time, &b.path).cmp(&(a.time, &a.path)));
+    snapshots
+}
+
+/// 残す数と日数を超えたスナップショットを返します。`snapshots`は新しい順に並んでいる必要があります。
+pub fn select_expired(snapshots: &[Snapshot], now: NaiveDateTime, retention: Retention) -> Vec<&Snapshot> {
+    snapshots
+        .iter()
+        .enumerate()
+        .filter(|(i, snapshot)| {
+            retention.max_count.is_some_and(|count| *i >= count)
+                || retention.max_days.is_some_and(|days| snapshot.time < now - Duration::days(days))
+        })
+        .map(|(_, snapshot)| snapshot)
+        .collect()
+}
+
+/// メモを書き換える前に、今の中身をスナップショットとして残します。
+/// 直前のスナップショットと同じ中身の場合やメモが無い場合は何もしません。
+pub fn save_snapshot(path: &Path, now: NaiveDateTime, retention: Retention) -> Result<Option<PathBuf>> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(_) => return Ok(None),
+    };
+    let snapshots = list_snapshots(path);
+    if snapshots.first().is_some_and(|latest| fs::read(&latest.path).is_ok_and(|x| x == bytes)) {
+        return Ok(None);
+    }
+
+    let dir = get_history_dir(path);
+    fs::create_dir_all(&dir).with_context(|| format!("can't create {}", dir.display()))?;
+    let ext = path.extension().map(|x| format!(".{}", x.to_string_lossy())).unwrap_or_default();
+    let stem = now.format(FORMAT_SNAPSHOT).to_string();
+    let mut path_snapshot = dir.join(format!("{}{}", stem, ext));
+    let mut n = 2;
+    while path_snapshot.exists() {
+        path_snapshot = dir.join(format!("{}_{}{}", stem, n, ext));
+        n += 1;
+    }
+    fs::write(&path_snapshot, bytes).with_context(|| format!("can't write {}", path_snapshot.display()))?;
+
+    for snapshot in select_expired(&list_snapshots(path), now, retention) {
+        fs::remove_file(&snapshot.path)?;
+    }
+    Ok(Some(path_snapshot))
+}
+
+/// `2021-10-03 12:00:05`、`2021-10-03 12:00`、`2021-10-03`(その日の終わり)、`20211003-120005`のような時刻を読みます。
+pub fn parse_time(time: &str) -> Result<NaiveDateTime> {
+    let time = time.trim().replace('T', " ");
+    for format in &["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", FORMAT_SNAPSHOT] {
+        if let Ok(time) = NaiveDateTime::parse_from_str(&time, format) {
+            return Ok(time);
+        }
+    }
+    match NaiveDate::parse_from_str(&time, "%Y-%m-%d").ok().and_then(|x| x.and_hms_opt(23, 59, 59)) {
+        Some(time) => Ok(time),
+        None => bail!("time must be like 2021-10-03 12:00: {}", time),
+    }
+}
+
+/// `time`の時点のスナップショット(`time`以前で一番新しいもの)を探します。
+pub fn find_snapshot_at(snapshots: &[Snapshot], time: NaiveDateTime) -> Option<&Snapshot> {
+    snapshots.iter().find(|snapshot| snapshot.time <= time)
+}
+
+/// メモをスナップショットの中身に戻します。戻す前の中身もスナップショットとして残します。
+pub fn restore(path: &Path, snapshot: &Snapshot, now: NaiveDateTime, retention: Retention) -> Result<()> {
+    let bytes = fs::read(&snapshot.path).with_context(|| format!("can't read {}", snapshot.path.display()))?;
+    save_snapshot(path, now, retention)?;
+    fs::write(path, bytes).with_context(|| format!("can't write {}", path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn select_expired_test() {
+        let time = |s: &str| parse_time(s).unwrap();
+        let snapshots: Vec<Snapshot> = ["2021-10-03 12:00", "2021-10-02 12:00", "2021-09-01 12:00"]
+            .iter()
+            .map(|x| Snapshot { path: PathBuf::from(x), time: time(x) })
+            .collect();
+        let now = time("2021-10-03 13:00");
+        let expired = |max_count, max_days| -> Vec<NaiveDateTime> {
+            select_expired(&snapshots, now, Retention { max_count, max_days }).iter().map(|x| x.time).collect()
+        };
+        assert_eq!(expired(None, None), vec![]);
+        assert_eq!(expired(Some(1), None), vec![time("2021-10-02 12:00"), time("2021-09-01 12:00")]);
+        assert_eq!(expired(None, Some(30)), vec![time("2021-09-01 12:00")]);
+
+        assert_eq!(find_snapshot_at(&snapshots, time("2021-10-02")).unwrap().time, time("2021-10-02 12:00"));
+        assert_eq!(find_snapshot_at(&snapshots, time("20210902-000000")).unwrap().time, time("2021-09-01 12:00"));
+        assert!(find_snapshot_at(&snapshots, time("2021-08-01 00:00:00")).is_none());
+        assert!(parse_time("yesterday").is_err());
+    }
+}
@@ -0,0 +1,577 @@
+use crate::check;
+use crate::memo;
+use crate::mv;
+use crate::path;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use regex::{Captures, Regex};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 添付ファイルの中身
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttachmentSource {
+    File(PathBuf),
+    Data(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attachment {
+    pub name: String,
+    pub source: AttachmentSource,
+}
+
+/// 取り込むノート1つ。本文の添付ファイルへのリンクは`attachment_link`で書きます。
+#[derive(Debug, Clone, PartialEq)]
+pub struct Note {
+    /// 元のファイル名(拡張子なし)。他のノートへのリンクは`[[元のファイル名]]`で書きます。
+    pub name: Option<String>,
+    pub title: String,
+    pub tags: Vec<String>,
+    pub created: Option<NaiveDate>,
+    pub body: String,
+    pub attachments: Vec<Attachment>,
+}
+
+/// 添付ファイルへのリンク。`write_note`で`assets/<メモ>/`からの相対パスに書き換えます。
+fn attachment_link(name: &str) -> String {
+    format!("{}[{}]({})", if path::is_image(name) { "!" } else { "" }, name, path::encode_link(name))
+}
+
+/// 同じ名前の添付ファイルが無ければ追加し、付けた名前を返します。
+fn push_attachment(attachments: &mut Vec<Attachment>, name: &str, source: AttachmentSource) -> String {
+    if let Some(attachment) = attachments.iter().find(|x| x.source == source) {
+        return attachment.name.clone();
+    }
+    let mut name = path::sanitize_file_name(name);
+    let mut n = 2;
+    while attachments.iter().any(|x| x.name == name) {
+        name = match name.rfind('.') {
+            Some(dot) => format!("{}_{}{}", &name[..dot], n, &name[dot..]),
+            None => format!("{}_{}", name, n),
+        };
+        n += 1;
+    }
+    attachments.push(Attachment { name: name.clone(), source });
+    name
+}
+
+/// タグの空白は`_`にします。menmaのタグは空白で区切るため。
+fn sanitize_tag(tag: &str) -> String {
+    tag.trim().trim_start_matches('#').split_whitespace().collect::<Vec<_>>().join("_")
+}
+
+fn parse_date(date: &str) -> Option<NaiveDate> {
+    let date = date.trim().trim_matches(|c| c == '"' || c == '\'');
+    NaiveDate::parse_from_str(date.get(..10)?, "%Y-%m-%d").ok()
+}
+
+/// Markdownの先頭の`---`で囲まれたフロントマター
+#[derive(Debug, Default, PartialEq)]
+struct FrontMatter {
+    title: Option<String>,
+    tags: Vec<String>,
+    created: Option<NaiveDate>,
+}
+
+/// フロントマターを読み、残りの本文と一緒に返します。`tags: [a, b]`、`tags: a b`、`- a`の並びを読めます。
+fn split_front_matter(text: &str) -> (FrontMatter, &str) {
+    let mut front_matter = FrontMatter::default();
+    let rest = match text.strip_prefix("---\n").or_else(|| text.strip_prefix("---\r\n")) {
+        Some(rest) => rest,
+        None => return (front_matter, text),
+    };
+    let (yaml, body) = match rest.find("\n---") {
+        Some(end) => {
+            let body = &rest[end + "\n---".len()..];
+            (&rest[..end], &body[body.find('\n').map_or(body.len(), |x| x + 1)..])
+        }
+        None => return (front_matter, text),
+    };
+    let unquote = |s: &str| s.trim().trim_matches(|c| c == '"' || c == '\'').to_string();
+    let mut key = String::new();
+    for line in yaml.lines() {
+        if let Some(item) = line.trim_start().strip_prefix("- ") {
+            if key == "tags" {
+                front_matter.tags.push(unquote(item));
+            }
+            continue;
+        }
+        let colon = match line.find(':') {
+            Some(colon) => colon,
+            None => continue,
+        };
+        key = line[..colon].trim().to_lowercase();
+        let value = line[colon + 1..].trim();
+        match key.as_str() {
+            "title" => front_matter.title = Some(unquote(value)).filter(|x| !x.is_empty()),
+            "created" | "date" | "created_time" => front_matter.created = front_matter.created.or_else(|| parse_date(value)),
+            "tags" | "tag" => {
+                key = "tags".to_string();
+                let value = value.trim_start_matches('[').trim_end_matches(']');
+                front_matter.tags.extend(
+                    value
+                        .split(|c: char| c == ',' || c.is_whitespace())
+                        .map(unquote)
+                        .filter(|x| !x.is_empty()),
+                );
+            }
+            _ => {}
+        }
+    }
+    (front_matter, body)
+}
+
+/// 本文の`#tag`を集めます。コードブロックの中と数字だけのもの(`#1`)は除きます。
+fn extract_inline_tags(text: &str) -> Vec<String> {
+    let re = Regex::new(r#"(?:^|\s)#([^\s#\[\](){},.;:!?'"]+)"#).unwrap();
+    let mut tags: Vec<String> = Vec::new();
+    let mut is_code_block = false;
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            is_code_block = !is_code_block;
+            continue;
+        }
+        if is_code_block {
+            continue;
+        }
+        for cap in re.captures_iter(line) {
+            let tag = cap[1].to_string();
+            if !tag.chars().all(|c| c.is_ascii_digit()) && !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+    }
+    tags
+}
+
+/// ディレクトリの下の全てのファイル。`.obsidian`のような`.`で始まるディレクトリは除きます。
+fn walk_dir(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("can't read {}", dir.display()))? {
+        let path = entry?.path();
+        if path.file_name().is_some_and(|x| x.to_string_lossy().starts_with('.')) {
+            continue;
+        }
+        if path.is_dir() {
+            files.extend(walk_dir(&path)?);
+        } else {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn is_markdown(path: &Path) -> bool {
+    path.extension().is_some_and(|x| x.eq_ignore_ascii_case("md"))
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem().map(|x| x.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+/// ObsidianのVaultやJoplinの「Markdown + Front Matter」のエクスポートから、Markdownのノートを読みます。
+/// `![[file]]`や`![alt](path)`の添付ファイル、他のノートへの`[text](note.md)`も書き換えます。
+pub fn read_markdown_notes(root: &Path) -> Result<Vec<Note>> {
+    let files = walk_dir(root)?;
+    // `![[image.png]]`はVaultの中のどこにあっても良いので、ファイル名から引く
+    let mut files_by_name: HashMap<String, PathBuf> = HashMap::new();
+    for file in &files {
+        if let Some(name) = file.file_name() {
+            files_by_name.entry(name.to_string_lossy().to_lowercase()).or_insert_with(|| file.clone());
+        }
+    }
+    let re_embed = Regex::new(r"!\[\[([^\]|]+)(?:\|[^\]]*)?\]\]").unwrap();
+    let re_link = Regex::new(r"(!?)\[([^\]]*)\]\(([^)\s]+)\)").unwrap();
+
+    let mut notes: Vec<Note> = Vec::new();
+    for path in files.iter().filter(|x| is_markdown(x)) {
+        let text = memo::read_text(path).with_context(|| format!("can't read {}", path.display()))?;
+        let (front_matter, body) = split_front_matter(&text);
+        let dir = path.parent().unwrap_or(root);
+        let mut attachments: Vec<Attachment> = Vec::new();
+
+        let body = re_embed.replace_all(body, |cap: &Captures| {
+            let name = cap[1].trim();
+            match files_by_name.get(&name.to_lowercase()) {
+                Some(file) if !is_markdown(file) => {
+                    attachment_link(&push_attachment(&mut attachments, name, AttachmentSource::File(file.clone())))
+                }
+                // ノートの埋め込みはリンクにする
+                _ => format!("[[{}]]", name.trim_end_matches(".md")),
+            }
+        });
+        let body = re_link.replace_all(&body, |cap: &Captures| {
+            let url = &cap[3];
+            if check::is_external_url(url) {
+                return cap[0].to_string();
+            }
+            let local = check::to_local_path(url);
+            let file = [dir.join(&local), root.join(&local)].iter().find(|x| x.is_file()).cloned();
+            match file {
+                Some(file) if is_markdown(&file) => format!("[[{}|{}]]", file_stem(&file), &cap[2]),
+                Some(file) => {
+                    let name = file.file_name().map(|x| x.to_string_lossy().into_owned()).unwrap_or_default();
+                    let name = push_attachment(&mut attachments, &name, AttachmentSource::File(file));
+                    let link = attachment_link(&name);
+                    // 元の`!`の有無と表示名を残す
+                    format!("{}[{}]{}", &cap[1], &cap[2], &link[link.find("](").unwrap() + 1..])
+                }
+                None => cap[0].to_string(),
+            }
+        });
+
+        let mut tags: Vec<String> = front_matter.tags.iter().map(|x| sanitize_tag(x)).collect();
+        for tag in extract_inline_tags(&body) {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+        let created = front_matter.created.or_else(|| {
+            let metadata = fs::metadata(path).ok()?;
+            let time = metadata.created().or_else(|_| metadata.modified()).ok()?;
+            Some(DateTime::<Local>::from(time).date_naive())
+        });
+        notes.push(Note {
+            name: Some(file_stem(path)),
+            title: front_matter.title.unwrap_or_else(|| file_stem(path)),
+            tags: tags.into_iter().filter(|x| !x.is_empty()).collect(),
+            created,
+            body: body.into_owned(),
+            attachments,
+        });
+    }
+    Ok(notes)
+}
+
+/// base64を読みます。空白と改行は無視します。
+fn decode_base64(text: &str) -> Result<Vec<u8>> {
+    let text: String = text.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+    Ok(base64::decode(text)?)
+}
+
+/// Evernoteの`<en-media hash="...">`と添付ファイルを結びつけるためのMD5
+fn md5_hex(data: &[u8]) -> String {
+    format!("{:x}", md5::compute(data))
+}
+
+fn get_attribute(e: &BytesStart, key: &[u8]) -> Option<String> {
+    e.attributes()
+        .filter_map(|x| x.ok())
+        .find(|x| x.key == key)
+        .and_then(|x| x.unescaped_value().ok().map(|x| String::from_utf8_lossy(&x).into_owned()))
+}
+
+/// 改行が無ければ改行します。
+fn push_line_break(out: &mut String) {
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+/// Evernoteの本文(ENML)をMarkdownにします。`<en-media>`は`media`(ハッシュから添付ファイル名)で添付ファイルへのリンクにします。
+fn enml_to_markdown(enml: &str, media: &HashMap<String, String>) -> Result<String> {
+    // XMLに無い実体参照
+    let enml = enml.replace("&nbsp;", "&#160;");
+    let mut reader = Reader::from_str(&enml);
+    reader.check_end_names(false);
+    let mut buf = Vec::new();
+    let mut out = String::new();
+    let mut hrefs: Vec<Option<String>> = Vec::new();
+    loop {
+        match reader.read_event(&mut buf).with_context(|| format!("invalid ENML at {}", reader.buffer_position()))? {
+            Event::Start(e) | Event::Empty(e) => {
+                match e.name() {
+                    b"br" => out.push('\n'),
+                    b"hr" => {
+                        push_line_break(&mut out);
+                        out += "---\n";
+                    }
+                    b"div" | b"p" | b"ul" | b"ol" | b"table" | b"tr" | b"blockquote" | b"pre" => push_line_break(&mut out),
+                    b"h1" | b"h2" | b"h3" | b"h4" | b"h5" | b"h6" => {
+                        push_line_break(&mut out);
+                        out += "# ";
+                    }
+                    b"li" => {
+                        push_line_break(&mut out);
+                        out += "- ";
+                    }
+                    b"en-todo" => {
+                        let is_checked = get_attribute(&e, b"checked").is_some_and(|x| x == "true");
+                        if out.is_empty() || out.ends_with('\n') {
+                            out += "- ";
+                        }
+                        out += if is_checked { "[x] " } else { "[ ] " };
+                    }
+                    b"en-media" => {
+                        if let Some(name) = get_attribute(&e, b"hash").and_then(|hash| media.get(&hash)) {
+                            out += &attachment_link(name);
+                        }
+                    }
+                    b"a" => {
+                        out.push('[');
+                        hrefs.push(get_attribute(&e, b"href"));
+                    }
+                    b"b" | b"strong" => out.push('*'),
+                    _ => {}
+                }
+            }
+            Event::End(e) => match e.name() {
+                b"div" | b"p" | b"li" | b"tr" | b"blockquote" | b"pre" | b"h1" | b"h2" | b"h3" | b"h4" | b"h5" | b"h6" => {
+                    push_line_break(&mut out)
+                }
+                b"td" | b"th" => out.push(' '),
+                b"a" => match hrefs.pop().flatten() {
+                    Some(href) => out += &format!("]({})", href),
+                    None => out.push(']'),
+                },
+                b"b" | b"strong" => out.push('*'),
+                _ => {}
+            },
+            Event::Text(e) => {
+                let text = e
+                    .unescape_and_decode(&reader)
+                    .unwrap_or_else(|_| String::from_utf8_lossy(e.escaped()).into_owned());
+                out += &text.replace('\u{a0}', " ").replace('\n', "");
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    let re_blank = Regex::new(r"\n{3,}").unwrap();
+    let out = re_blank.replace_all(out.trim(), "\n\n");
+    Ok(format!("{}\n", out))
+}
+
+/// `20211003T120000Z`
+fn parse_enex_date(date: &str) -> Option<NaiveDate> {
+    NaiveDateTime::parse_from_str(date.trim(), "%Y%m%dT%H%M%SZ").ok().map(|x| x.date())
+}
+
+/// Evernoteのエクスポート(`.enex`)を読みます。
+pub fn read_enex_notes(xml: &str) -> Result<Vec<Note>> {
+    let mut reader = Reader::from_str(xml);
+    let mut buf = Vec::new();
+    let mut notes: Vec<Note> = Vec::new();
+    let mut path: Vec<Vec<u8>> = Vec::new();
+    let mut note: Option<Note> = None;
+    let mut content = String::new();
+    // (ファイル名, base64の中身)
+    let mut resources: Vec<(String, String)> = Vec::new();
+    loop {
+        match reader.read_event(&mut buf).context("can't read enex")? {
+            Event::Start(e) => {
+                match e.name() {
+                    b"note" => {
+                        note = Some(Note {
+                            name: None,
+                            title: String::new(),
+                            tags: Vec::new(),
+                            created: None,
+                            body: String::new(),
+                            attachments: Vec::new(),
+                        });
+                        content.clear();
+                        resources.clear();
+                    }
+                    b"resource" => resources.push((String::new(), String::new())),
+                    _ => {}
+                }
+                path.push(e.name().to_vec());
+            }
+            Event::End(e) => {
+                path.pop();
+                if e.name() == b"note" {
+                    if let Some(mut note) = note.take() {
+                        let mut media: HashMap<String, String> = HashMap::new();
+                        for (i, (name, data)) in resources.iter().enumerate() {
+                            let data = decode_base64(data)
+                                .with_context(|| format!("can't decode an attachment of \"{}\"", note.title))?;
+                            let name = if name.is_empty() { format!("attachment{}", i + 1) } else { name.clone() };
+                            let hash = md5_hex(&data);
+                            let name = push_attachment(&mut note.attachments, &name, AttachmentSource::Data(data));
+                            media.insert(hash, name);
+                        }
+                        note.body = enml_to_markdown(&content, &media)
+                            .with_context(|| format!("can't read the content of \"{}\"", note.title))?;
+                        notes.push(note);
+                    }
+                }
+            }
+            Event::Text(e) | Event::CData(e) => {
+                let text = match e.unescape_and_decode(&reader) {
+                    Ok(text) => text,
+                    Err(_) => String::from_utf8_lossy(e.escaped()).into_owned(),
+                };
+                let note = match note.as_mut() {
+                    Some(note) => note,
+                    None => continue,
+                };
+                let parent = path.last().map(|x| x.as_slice());
+                let is_in_resource = path.iter().any(|x| x == b"resource");
+                match parent {
+                    Some(b"title") if !is_in_resource => note.title += text.trim(),
+                    Some(b"created") if !is_in_resource => note.created = parse_enex_date(&text),
+                    Some(b"tag") => note.tags.push(sanitize_tag(&text)),
+                    Some(b"content") => content += &text,
+                    Some(b"data") if is_in_resource => {
+                        if let Some(resource) = resources.last_mut() {
+                            resource.1 += &text;
+                        }
+                    }
+                    Some(b"file-name") if is_in_resource => {
+                        if let Some(resource) = resources.last_mut() {
+                            resource.0 = text.trim().to_string();
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(notes)
+}
+
+/// ノートを書き込む`dir_memo`のパスを決めます。ファイル名は`add`と同じ`yymmdd_タイトル.md`で、
+/// 既にあるメモや他のノートと重なる場合は`_2`のように番号を付けます。
+pub fn assign_note_paths(notes: &[Note], dir_memo: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = Vec::new();
+    for note in notes {
+        let date = note.created.unwrap_or_else(|| Local::now().date_naive());
+        let title = match path::sanitize_file_name(&note.title) {
+            title if title.is_empty() => "untitled".to_string(),
+            title => title,
+        };
+        let prefix = date.format("%y%m%d_").to_string();
+        let mut path = dir_memo.join(format!("{}{}.md", prefix, title));
+        let mut n = 2;
+        while path.exists() || paths.contains(&path) {
+            path = dir_memo.join(format!("{}{}_{}.md", prefix, title, n));
+            n += 1;
+        }
+        paths.push(path);
+    }
+    paths
+}
+
+/// 他のノートへの`[[元のファイル名]]`のリンクを、`assign_note_paths`で決めたメモへのリンクに書き換えます。
+/// メモのタイトルで辿れるリンクはそのままにします。
+pub fn rewrite_note_links(notes: &mut [Note], paths: &[PathBuf]) {
+    let stems: HashMap<String, String> = notes
+        .iter()
+        .zip(paths)
+        .filter_map(|(note, path)| Some((note.name.as_ref()?.to_lowercase(), file_stem(path))))
+        .collect();
+    let rename_wiki = |target: &str| {
+        let target = target.trim().to_lowercase();
+        let target = target.strip_suffix(".md").unwrap_or(&target);
+        let stem = stems.get(target)?;
+        let title = memo::Memo::new(format!("{}.md", stem), Vec::new()).get_title();
+        Some(stem.clone()).filter(|_| title.to_lowercase() != target)
+    };
+    for note in notes.iter_mut() {
+        if let Some(body) = mv::rewrite_links(&note.body, rename_wiki, |_| None) {
+            note.body = body;
+        }
+    }
+}
+
+/// ノートを`assign_note_paths`で決めた`path`にメモとして書き込みます。
+/// 添付ファイルは`assets/<メモのファイル名>/`にコピーします。
+pub fn write_note(note: &Note, path: &Path) -> Result<()> {
+    let mut body = note.body.clone();
+    if !note.attachments.is_empty() {
+        let dir_assets = memo::get_assets_dir(path);
+        fs::create_dir_all(&dir_assets).with_context(|| format!("can't create {}", dir_assets.display()))?;
+        let dir_link = format!("{}/{}", memo::DIR_ASSETS, path::encode_link(&file_stem(path)));
+        for attachment in &note.attachments {
+            let dest = dir_assets.join(&attachment.name);
+            match &attachment.source {
+                AttachmentSource::File(source) => {
+                    fs::copy(source, &dest).with_context(|| format!("can't copy {}", source.display()))?;
+                }
+                AttachmentSource::Data(data) => fs::write(&dest, data)?,
+            }
+            let link = format!("]({})", path::encode_link(&attachment.name));
+            body = body.replace(&link, &format!("]({}/{})", dir_link, path::encode_link(&attachment.name)));
+        }
+    }
+
+    let mut contents = memo::create_tags_header(&note.tags);
+    contents += &body;
+    if !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    fs::write(path, contents).with_context(|| format!("can't write {}", path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn split_front_matter_test() {
+        let text = "---\ntitle: \"Weekly\"\ncreated: 2021-10-03 12:00:00Z\ntags:\n  - work\n  - team notes\n---\nbody #rust/egui and #1\n";
+        let (front_matter, body) = split_front_matter(text);
+        assert_eq!(front_matter.title.as_deref(), Some("Weekly"));
+        assert_eq!(front_matter.created, NaiveDate::from_ymd_opt(2021, 10, 3));
+        assert_eq!(front_matter.tags, vec!["work", "team notes"]);
+        assert_eq!(body, "body #rust/egui and #1\n");
+        assert_eq!(extract_inline_tags(body), vec!["rust/egui"]);
+        assert_eq!(split_front_matter("---\ntags: [a, b]\n---\n").0.tags, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn read_enex_notes_test() {
+        assert_eq!(md5_hex(b""), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(md5_hex(b"abc"), "900150983cd24fb0d6963f7d28e17f72");
+        let enex = r#"<?xml version="1.0" encoding="UTF-8"?>
+<en-export><note><title>Trip plan</title>
+<content><![CDATA[<?xml version="1.0" encoding="UTF-8"?><!DOCTYPE en-note SYSTEM "http://xml.evernote.com/pub/enml2.dtd">
+<en-note><div>Day&nbsp;1</div><div><en-todo checked="true"/>book hotel</div><div><en-media hash="900150983cd24fb0d6963f7d28e17f72" type="image/png"/></div></en-note>]]></content>
+<created>20211003T120000Z</created><tag>travel</tag><tag>family trip</tag>
+<resource><data encoding="base64">YWJj</data><mime>image/png</mime><resource-attributes><file-name>map.png</file-name></resource-attributes></resource>
+</note></en-export>"#;
+        let notes = read_enex_notes(enex).unwrap();
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].title, "Trip plan");
+        assert_eq!(notes[0].tags, vec!["travel", "family_trip"]);
+        assert_eq!(notes[0].created, NaiveDate::from_ymd_opt(2021, 10, 3));
+        assert_eq!(notes[0].body, "Day 1\n- [x] book hotel\n![map.png](map.png)\n");
+        assert_eq!(notes[0].attachments[0].source, AttachmentSource::Data(b"abc".to_vec()));
+
+        let enex = "<en-export><note><title>Broken</title><content>&lt;en-note&gt;&lt;!-- x</content></note></en-export>";
+        let error = read_enex_notes(enex).unwrap_err();
+        assert_eq!(error.to_string(), "can't read the content of \"Broken\"");
+    }
+
+    #[test]
+    fn rewrite_note_links_test() {
+        let note = |name: &str, title: &str, body: &str| Note {
+            name: Some(name.to_string()),
+            title: title.to_string(),
+            tags: Vec::new(),
+            created: NaiveDate::from_ymd_opt(2021, 10, 3),
+            body: body.to_string(),
+            attachments: Vec::new(),
+        };
+        let mut notes = vec![
+            note("plan", "Plan", "[[design|the design]] [[Design.md]] [[plan]]"),
+            note("design", "Weekly", ""),
+            note("Plan 2", "Plan", "[[plan]] [[plan 2]]"),
+        ];
+        let paths = assign_note_paths(&notes, Path::new("no_such_dir"));
+        assert_eq!(paths[2], Path::new("no_such_dir").join("211003_Plan_2.md"));
+        rewrite_note_links(&mut notes, &paths);
+        assert_eq!(notes[0].body, "[[211003_Weekly|the design]] [[211003_Weekly]] [[plan]]");
+        assert_eq!(notes[2].body, "[[plan]] [[211003_Plan_2]]");
+    }
+}
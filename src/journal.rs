@@ -0,0 +1,84 @@
+use crate::memo;
+use crate::template;
+use anyhow::{bail, Result};
+use chrono::{Duration, Local, NaiveDate, TimeZone};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 日誌に自動で付けるタグ
+pub const TAG_JOURNAL: &str = "journal";
+
+/// `today`、`yesterday`、`2021-10-03`のような日付を読みます。
+pub fn parse_journal_date(date: &str, today: NaiveDate) -> Result<NaiveDate> {
+    match date {
+        "today" => Ok(today),
+        "yesterday" => Ok(today - Duration::days(1)),
+        _ => match NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+            Ok(date) => Ok(date),
+            Err(_) => bail!("date must be today, yesterday or yyyy-mm-dd: {}", date),
+        },
+    }
+}
+
+/// その日の日誌のパス(`yymmdd_journal.md`)
+pub fn get_journal_path(dir_journal: &str, date: NaiveDate) -> PathBuf {
+    Path::new(dir_journal).join(format!("{}_{}.md", date.format("%y%m%d"), TAG_JOURNAL))
+}
+
+/// その日の日誌が無ければテンプレートから作り、パスを返します。
+pub fn create_journal_if_not_exist(dir_journal: &str, date: NaiveDate, template: Option<&str>) -> Result<PathBuf> {
+    let path = get_journal_path(dir_journal, date);
+    if !path.exists() {
+        let now = Local::now();
+        let time = Local
+            .from_local_datetime(&date.and_time(now.time()))
+            .single()
+            .unwrap_or(now);
+        let contents = template::create_memo_contents(template, TAG_JOURNAL, &[TAG_JOURNAL.to_string()], &time);
+        fs::write(&path, contents)?;
+    }
+    Ok(path)
+}
+
+/// 直近`days`日分の日誌を新しい順に返します。
+pub fn filter_journal(lst_memo: &[memo::Memo], today: NaiveDate, days: u32) -> Vec<memo::Memo> {
+    let since = today - Duration::days(i64::from(days) - 1);
+    let mut lst_journal: Vec<memo::Memo> = lst_memo
+        .iter()
+        .filter(|memo| memo.get_tags().iter().any(|tag| tag == TAG_JOURNAL))
+        .filter(|memo| match memo.get_date_created() {
+            Some(date) => since <= date && date <= today,
+            None => false,
+        })
+        .cloned()
+        .collect();
+    lst_journal.sort_by_key(|memo| std::cmp::Reverse(memo.get_date_created()));
+    lst_journal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn parse_journal_date_test() {
+        let today = NaiveDate::from_ymd_opt(2021, 10, 1).unwrap();
+        assert_eq!(parse_journal_date("today", today).unwrap(), today);
+        assert_eq!(parse_journal_date("yesterday", today).unwrap(), NaiveDate::from_ymd_opt(2021, 9, 30).unwrap());
+        assert_eq!(parse_journal_date("2021-10-17", today).unwrap(), NaiveDate::from_ymd_opt(2021, 10, 17).unwrap());
+        assert!(parse_journal_date("10/17", today).is_err());
+    }
+
+    #[test]
+    fn filter_journal_test() {
+        let today = NaiveDate::from_ymd_opt(2021, 10, 3).unwrap();
+        let journal = vec![TAG_JOURNAL.to_string()];
+        let lst_memo = vec![
+            memo::Memo::new("211002_journal.md".to_string(), journal.clone()),
+            memo::Memo::new("211003_journal.md".to_string(), journal.clone()),
+            memo::Memo::new("210926_journal.md".to_string(), journal),
+            memo::Memo::new("211003_other.md".to_string(), vec!["work".to_string()]),
+        ];
+        let lst_journal: Vec<String> = filter_journal(&lst_memo, today, 7).iter().map(|x| x.get_path().clone()).collect();
+        assert_eq!(lst_journal, vec!["211003_journal.md", "211002_journal.md"]);
+    }
+}
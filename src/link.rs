@@ -0,0 +1,126 @@
+use crate::memo;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// メモ本文の`[[target]]`、`[[target|label]]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct WikiLink {
+    pub target: String,
+    pub label: String,
+    /// 1から始まる行番号
+    pub line: usize,
+}
+
+/// `[[`の直後から`]]`までを読み、(リンク先, 表示名, `]]`の後ろまでのバイト数)を返します。
+pub fn parse_wiki_link(s: &str) -> Option<(&str, &str, usize)> {
+    let rest = s.strip_prefix("[[")?;
+    let this_line = &rest[..rest.find('\n').unwrap_or(rest.len())];
+    let end = this_line.find("]]")?;
+    let inner = &this_line[..end];
+    if inner.trim().is_empty() {
+        return None;
+    }
+    let (target, label) = match inner.find('|') {
+        Some(bar) => (&inner[..bar], &inner[bar + 1..]),
+        None => (inner, inner),
+    };
+    Some((target.trim(), label.trim(), 2 + end + 2))
+}
+
+/// 本文から`[[link]]`を全て取り出します。コードブロックの中は無視します。
+pub fn extract_wiki_links(text: &str) -> Vec<WikiLink> {
+    let mut links: Vec<WikiLink> = Vec::new();
+    let mut is_code_block = false;
+    for (i, line) in text.lines().enumerate() {
+        if line.trim_start().starts_with("```") {
+            is_code_block = !is_code_block;
+            continue;
+        }
+        if is_code_block {
+            continue;
+        }
+        let mut rest = line;
+        while let Some(start) = rest.find("[[") {
+            match parse_wiki_link(&rest[start..]) {
+                Some((target, label, len)) => {
+                    links.push(WikiLink {
+                        target: target.to_string(),
+                        label: label.to_string(),
+                        line: i + 1,
+                    });
+                    rest = &rest[start + len..];
+                }
+                None => rest = &rest[start + 2..],
+            }
+        }
+    }
+    links
+}
+
+/// リンク先の名前に一致するメモを探します。
+/// タイトル(`yymmdd_`を除いた名前)とファイル名のどちらでも、大文字小文字を区別せずに一致させます。
+pub fn resolve_wiki_link<'a>(target: &str, lst_memo: &'a [memo::Memo]) -> Option<&'a memo::Memo> {
+    let target = target.trim().to_lowercase();
+    let target = target.strip_suffix(".md").unwrap_or(&target);
+    // 同じタイトルのメモが複数ある場合に備えて、ファイル名での一致を優先する
+    let is_match_filename = |memo: &&memo::Memo| {
+        Path::new(memo.get_path())
+            .file_stem()
+            .is_some_and(|stem| stem.to_string_lossy().to_lowercase() == target)
+    };
+    lst_memo
+        .iter()
+        .find(is_match_filename)
+        .or_else(|| lst_memo.iter().find(|memo| memo.get_title().to_lowercase() == target))
+}
+
+/// リンク先のメモのパスから、そのメモへリンクしているメモのパスを引く索引を作ります。
+pub fn create_backlink_index(lst_memo: &[memo::Memo]) -> HashMap<String, Vec<String>> {
+    let mut backlinks: HashMap<String, Vec<String>> = HashMap::new();
+    for memo in lst_memo {
+        for target in memo.get_links() {
+            if let Some(memo_target) = resolve_wiki_link(target, lst_memo) {
+                let sources = backlinks.entry(memo_target.get_path().clone()).or_default();
+                if !sources.contains(memo.get_path()) {
+                    sources.push(memo.get_path().clone());
+                }
+            }
+        }
+    }
+    backlinks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn extract_wiki_links_test() {
+        let text = "see [[Meeting]] and [[211003_design|the design]]\n```\n[[not a link]]\n```\n[[]] [[broken\n";
+        let links = extract_wiki_links(text);
+        assert_eq!(links.len(), 2);
+        assert_eq!((links[0].target.as_str(), links[0].label.as_str(), links[0].line), ("Meeting", "Meeting", 1));
+        assert_eq!((links[1].target.as_str(), links[1].label.as_str()), ("211003_design", "the design"));
+    }
+
+    #[test]
+    fn resolve_wiki_link_test() {
+        let lst_memo = vec![
+            memo::Memo::new("E:/memo/211001_meeting.md".to_string(), vec![]),
+            memo::Memo::new("E:/memo/211003_design.md".to_string(), vec![]),
+        ];
+        assert_eq!(resolve_wiki_link("Meeting", &lst_memo).unwrap().get_path(), "E:/memo/211001_meeting.md");
+        assert_eq!(resolve_wiki_link("211003_Design", &lst_memo).unwrap().get_path(), "E:/memo/211003_design.md");
+        assert!(resolve_wiki_link("unknown", &lst_memo).is_none());
+    }
+
+    #[test]
+    fn create_backlink_index_test() {
+        let lst_memo = vec![
+            memo::Memo::new("211001_meeting.md".to_string(), vec![]).with_links(vec!["design".to_string(), "Design".to_string()]),
+            memo::Memo::new("211003_design.md".to_string(), vec![]).with_links(vec!["unknown".to_string()]),
+        ];
+        let backlinks = create_backlink_index(&lst_memo);
+        assert_eq!(backlinks.get("211003_design.md"), Some(&vec!["211001_meeting.md".to_string()]));
+        assert_eq!(backlinks.get("211001_meeting.md"), None);
+    }
+}
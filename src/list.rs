@@ -0,0 +1,83 @@
+use crate::memo;
+use serde::Serialize;
+
+/// `list`の出力1行分
+#[derive(Debug, Serialize)]
+pub struct MemoRecord {
+    pub path: String,
+    pub title: String,
+    pub tags: Vec<String>,
+    pub created: Option<String>,
+    pub modified: Option<String>,
+}
+
+impl MemoRecord {
+    pub fn from_memo(memo: &memo::Memo) -> Self {
+        MemoRecord {
+            path: memo.get_path().clone(),
+            title: memo.get_title(),
+            tags: memo.get_tags().iter().map(|x| x.trim().to_string()).collect(),
+            created: memo.get_date_created().map(|x| x.format("%Y-%m-%d").to_string()),
+            modified: memo.get_date_modified().map(|x| x.format("%Y-%m-%d").to_string()),
+        }
+    }
+}
+
+/// メモ一覧を`plain`、`json`、`csv`、`paths`のいずれかの形式で標準出力に出します。
+pub fn print_memo_list(lst_memo: &[memo::Memo], format: &str) -> serde_json::Result<()> {
+    let records: Vec<MemoRecord> = lst_memo.iter().map(MemoRecord::from_memo).collect();
+    match format {
+        "json" => println!("{}", serde_json::to_string_pretty(&records)?),
+        "csv" => {
+            println!("path,title,tags,created,modified");
+            for record in &records {
+                let fields = [
+                    record.path.as_str(),
+                    record.title.as_str(),
+                    &record.tags.join(" "),
+                    record.created.as_deref().unwrap_or(""),
+                    record.modified.as_deref().unwrap_or(""),
+                ];
+                let fields: Vec<String> = fields.iter().map(|x| escape_csv(x)).collect();
+                println!("{}", fields.join(","));
+            }
+        }
+        "paths" => {
+            for record in &records {
+                println!("{}", record.path);
+            }
+        }
+        _ => {
+            for record in &records {
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    record.created.as_deref().unwrap_or("-"),
+                    record.title,
+                    record.tags.join(","),
+                    record.path
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// カンマ、ダブルクォート、改行を含む値をダブルクォートで囲みます。
+fn escape_csv(field: &str) -> String {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn escape_csv_test() {
+        assert_eq!(escape_csv("foo"), "foo");
+        assert_eq!(escape_csv("foo,bar"), "\"foo,bar\"");
+        assert_eq!(escape_csv("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
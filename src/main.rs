@@ -1,12 +1,12 @@
-use anyhow::{bail, Result};
-use chrono::{Utc};
+use anyhow::{bail, Context, Result};
+use chrono::{Local, Utc};
 use std::env;
 use encoding_rs;
 use std::error::Error;
 use std::io::BufReader;
 use std::fs;
 use serde::{Serialize, Deserialize};
-use std::io::{Write};
+use std::io::{IsTerminal, Read, Write};
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use structopt::{clap, StructOpt};
@@ -17,7 +17,25 @@ winrt::import!(
         windows::system::Launcher
 );
 
+mod archive;
+mod attach;
+mod check;
+mod dupes;
+mod export;
+mod git;
+mod graph;
+mod history;
+mod import;
+mod journal;
+mod link;
+mod list;
+mod markup;
 mod memo;
+mod mv;
+mod path;
+mod tag;
+mod task;
+mod template;
 mod tui;
 mod gui;
 
@@ -35,8 +53,18 @@ pub enum Sub {
     #[structopt(name = "list", about = "view list")]
     #[structopt(setting(clap::AppSettings::ColoredHelp))]
     List {
+        /// tags to filter by. if omitted, all memos are listed
         #[structopt(short = "t", long = "tags")]
         tags: Option<Vec<String>>,
+        /// print the list and exit instead of launching the tui
+        #[structopt(short = "f", long = "format", possible_values = &["plain", "json", "csv", "paths"])]
+        format: Option<String>,
+        /// launch the tui even if stdout is not a terminal
+        #[structopt(long = "tui")]
+        tui: bool,
+        /// include archived memos
+        #[structopt(long = "archived")]
+        archived: bool,
     },
     #[structopt(name = "add", about = "add memo")]
     #[structopt(setting(clap::AppSettings::ColoredHelp))]
@@ -45,6 +73,18 @@ pub enum Sub {
         title: String,
         #[structopt(short = "t", long = "tags")]
         tags: Option<Vec<String>>,
+        /// name of the template in path_template of setting.json (without .md)
+        #[structopt(long = "template")]
+        template: Option<String>,
+        /// body of the memo
+        #[structopt(short = "b", long = "body")]
+        body: Option<String>,
+        /// read the body from stdin. the memo isn't opened
+        #[structopt(long = "stdin", conflicts_with = "body")]
+        stdin: bool,
+        /// don't open the memo after creating it
+        #[structopt(long = "no-open")]
+        no_open: bool,
     },
     #[structopt(name = "setpath", about = "set path of memo exist directory")]
     #[structopt(setting(clap::AppSettings::ColoredHelp))]
@@ -55,14 +95,209 @@ pub enum Sub {
     #[structopt(name = "todo", about = "open todo.txt")]
     #[structopt(setting(clap::AppSettings::ColoredHelp))]
     Todo {},
+    #[structopt(name = "today", about = "open or create the journal of the day")]
+    #[structopt(setting(clap::AppSettings::ColoredHelp))]
+    Today {
+        /// today, yesterday or yyyy-mm-dd
+        #[structopt(short = "d", long = "date", default_value = "today")]
+        date: String,
+    },
+    #[structopt(name = "journal", about = "view journals of recent days")]
+    #[structopt(setting(clap::AppSettings::ColoredHelp))]
+    Journal {
+        /// number of recent days to show (1 or more)
+        #[structopt(short = "n", long = "days", default_value = "7")]
+        days: u32,
+    },
+    #[structopt(name = "tasks", about = "view tasks written as checkboxes in memos")]
+    #[structopt(setting(clap::AppSettings::ColoredHelp))]
+    Tasks {
+        /// only tasks in memos with these tags
+        #[structopt(short = "t", long = "tags")]
+        tags: Option<Vec<String>>,
+        /// include done tasks
+        #[structopt(short = "a", long = "all")]
+        all: bool,
+        /// only tasks past @due(yyyy-mm-dd)
+        #[structopt(long = "overdue")]
+        overdue: bool,
+        /// only tasks due within the period from today, like 3d or 2w
+        #[structopt(long = "due-within")]
+        due_within: Option<String>,
+        #[structopt(subcommand)]
+        sub: Option<SubTasks>,
+    },
+    #[structopt(name = "check", about = "check broken links in memos")]
+    #[structopt(setting(clap::AppSettings::ColoredHelp))]
+    Check {},
+    #[structopt(name = "graph", about = "print links and shared tags between memos as a graph")]
+    #[structopt(setting(clap::AppSettings::ColoredHelp))]
+    Graph {
+        /// only memos with these tags
+        #[structopt(short = "t", long = "tags")]
+        tags: Option<Vec<String>>,
+        #[structopt(short = "f", long = "format", default_value = "dot", possible_values = &["dot", "json"])]
+        format: String,
+    },
+    #[structopt(name = "export", about = "export memos to other formats")]
+    #[structopt(setting(clap::AppSettings::ColoredHelp))]
+    Export {
+        #[structopt(subcommand)]
+        sub: SubExport,
+    },
+    #[structopt(name = "import", about = "import notes from other apps as memos")]
+    #[structopt(setting(clap::AppSettings::ColoredHelp))]
+    Import {
+        /// obsidian: vault directory, joplin: directory exported as "Markdown + Front Matter", enex: .enex file
+        #[structopt(long = "from", possible_values = &["obsidian", "joplin", "enex"])]
+        from: String,
+        path: PathBuf,
+        /// directory to write memos. the first path_memo of setting.json is used if omitted
+        #[structopt(long = "to")]
+        to: Option<PathBuf>,
+    },
+    #[structopt(name = "log", about = "view revisions of the memo committed by use_git")]
+    #[structopt(setting(clap::AppSettings::ColoredHelp))]
+    Log {
+        /// title, file name or path of the memo
+        memo: String,
+    },
+    #[structopt(name = "show", about = "print an old revision of the memo")]
+    #[structopt(setting(clap::AppSettings::ColoredHelp))]
+    Show {
+        /// <memo>@<rev>, like design@HEAD~1
+        spec: String,
+    },
+    #[structopt(name = "history", about = "view snapshots of the memo taken before menma changed it")]
+    #[structopt(setting(clap::AppSettings::ColoredHelp))]
+    History {
+        /// title, file name or path of the memo
+        memo: String,
+    },
+    #[structopt(name = "restore", about = "restore the memo from a snapshot")]
+    #[structopt(setting(clap::AppSettings::ColoredHelp))]
+    Restore {
+        /// title, file name or path of the memo
+        memo: String,
+        /// restore the latest snapshot at this time, like "2021-10-03 12:00"
+        #[structopt(long = "at")]
+        at: String,
+    },
+    #[structopt(name = "archive", about = "move memos to the archive directory")]
+    #[structopt(setting(clap::AppSettings::ColoredHelp))]
+    Archive {
+        /// title, file name or path of the memo
+        memo: Option<String>,
+        /// all memos with these tags
+        #[structopt(short = "t", long = "tags")]
+        tags: Option<Vec<String>>,
+        /// don't ask when more than one memo is selected
+        #[structopt(short = "y", long = "yes")]
+        yes: bool,
+    },
+    #[structopt(name = "rm", about = "move memos to the trash")]
+    #[structopt(setting(clap::AppSettings::ColoredHelp))]
+    Rm {
+        /// title, file name or path of the memo
+        memo: Option<String>,
+        /// all memos with these tags
+        #[structopt(short = "t", long = "tags")]
+        tags: Option<Vec<String>>,
+        /// don't ask when more than one memo is selected
+        #[structopt(short = "y", long = "yes")]
+        yes: bool,
+    },
+    #[structopt(name = "mv", about = "rename or move the memo and update links to it")]
+    #[structopt(setting(clap::AppSettings::ColoredHelp))]
+    Mv {
+        /// title, file name or path of the memo
+        memo: String,
+        /// new title (the yymmdd_ prefix is kept) or directory to move the memo to
+        dest: String,
+    },
+    #[structopt(name = "attach", about = "copy files into the assets directory and link them from the memo")]
+    #[structopt(setting(clap::AppSettings::ColoredHelp))]
+    Attach {
+        /// title, file name or path of the memo
+        memo: String,
+        /// files to attach
+        #[structopt(required = true, parse(from_os_str))]
+        files: Vec<PathBuf>,
+        /// put the files in the assets directory shared by the memos in the same directory
+        #[structopt(short = "s", long = "shared")]
+        shared: bool,
+    },
+    #[structopt(name = "dupes", about = "find duplicated and similar memos")]
+    #[structopt(setting(clap::AppSettings::ColoredHelp))]
+    Dupes {
+        /// memos whose similarity is at least this value are grouped (0.0 - 1.0)
+        #[structopt(long = "threshold", default_value = "0.8")]
+        threshold: f64,
+        /// archive all memos but the oldest one in each group without asking
+        #[structopt(short = "a", long = "archive")]
+        archive: bool,
+    },
+    #[structopt(name = "trash", about = "view memos in the trash")]
+    #[structopt(setting(clap::AppSettings::ColoredHelp))]
+    Trash {
+        #[structopt(subcommand)]
+        sub: Option<SubTrash>,
+    },
+    #[structopt(name = "tags", about = "view tags with memo count")]
+    #[structopt(setting(clap::AppSettings::ColoredHelp))]
+    Tags {
+        #[structopt(short = "f", long = "format", default_value = "table", possible_values = &["table", "json"])]
+        format: String,
+        /// show the tags most often used together, up to this number
+        #[structopt(short = "c", long = "co-occurrence")]
+        co_occurrence: Option<usize>,
+    },
     #[structopt(name = "gui", about = "launch gui mode")]
     #[structopt(setting(clap::AppSettings::ColoredHelp))]
     GUI {},
 }
 
+#[derive(Debug, StructOpt)]
+pub enum SubTasks {
+    #[structopt(name = "done", about = "toggle the checkbox of the task")]
+    #[structopt(setting(clap::AppSettings::ColoredHelp))]
+    Done {
+        /// id shown by `tasks`, or its unique prefix
+        id: String,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub enum SubTrash {
+    #[structopt(name = "restore", about = "move the memo in the trash back to its directory")]
+    #[structopt(setting(clap::AppSettings::ColoredHelp))]
+    Restore {
+        /// file name or title of the memo in the trash. the newest one is restored if there are several
+        memo: String,
+    },
+    #[structopt(name = "empty", about = "delete memos in the trash")]
+    #[structopt(setting(clap::AppSettings::ColoredHelp))]
+    Empty {
+        /// delete without listing the memos first
+        #[structopt(short = "y", long = "yes")]
+        yes: bool,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub enum SubExport {
+    #[structopt(name = "html", about = "export memos as static html pages with tag and date indexes")]
+    #[structopt(setting(clap::AppSettings::ColoredHelp))]
+    Html {
+        /// directory to write html files
+        out_dir: PathBuf,
+    },
+}
+
 fn main() -> Result<()> {
     let args = Opt::from_args();
-    println!("{:?}", args);
+    // 引数の相対パスは実行ファイルの場所ではなく、実行したディレクトリから辿る
+    let dir_current = env::current_dir()?;
 
     // 設定ファイル読み込み
     let mut dir_exe = env::current_exe().unwrap();
@@ -76,53 +311,76 @@ fn main() -> Result<()> {
     let lst_memo = memo::create_memo_list(&setting.get_memo_path());
 
     match args.sub {
-        Sub::List { tags } => {
-            match tags {
-                Some(tags) => loop {
-                    let lst_memo_include_thesetags: Vec<memo::Memo> =
-                        if tags.iter().any(|x| x.contains("all")) {
-                            lst_memo.clone()
-                        } else {
-                            lst_memo
-                                .iter()
-                                .filter(|memo| memo::is_include_these_tags(&tags, memo.get_tags()))
-                                .cloned()
-                                .collect()
-                        };
-                    tui::launch_tui(&lst_memo_include_thesetags).unwrap();
-                },
-                None => {
-                    bail!("tag value is incorrect. please input valid value.")
-                }
+        Sub::List { tags, format, tui: is_tui, archived } => {
+            let mut lst_memo = lst_memo;
+            if archived {
+                let lst_dir: Vec<String> = archive::get_archive_dirs(&setting)
+                    .into_iter()
+                    .filter(|dir| Path::new(dir).is_dir())
+                    .collect();
+                lst_memo.extend(memo::create_memo_list(&lst_dir));
+            }
+            // タグを指定しなければ全てのメモ
+            let tags = tags.unwrap_or_default();
+            // 端末以外への出力(パイプ、リダイレクト)はスクリプト用に一覧を出して終わる
+            let format = match format {
+                Some(format) if !is_tui => Some(format),
+                None if !is_tui && !std::io::stdout().is_terminal() => Some("plain".to_string()),
+                _ => None,
+            };
+            if let Some(format) = format {
+                let lst_memo = if tags.is_empty() { lst_memo } else { memo::filter_memo_by_tags(&lst_memo, &tags) };
+                list::print_memo_list(&lst_memo, &format)?;
+                return Ok(());
             }
+            tui::launch_tui(&lst_memo, &tags).unwrap();
+            Ok(())
         }
-        Sub::Add { title, tags } => {
+        Sub::Add { title, tags, template, body, stdin, no_open } => {
             let path = Path::new("E:/memo/");
             let filename = Utc::now().format("%y%m%d_").to_string() + &title + ".md";
 
-            // 複数回実行した場合上書きされる
-            let mut file = match fs::File::create(path.to_str().unwrap().to_string() + &filename) {
-                Err(why) => panic!("Couldn't create {}", why),
-                Ok(file) => file,
+            let template = match template {
+                Some(name) => match setting.get_template_path() {
+                    Some(dir_template) => Some(template::read_template(dir_template, &name)?),
+                    None => bail!("path_template isn't set. Please add path_template to setting.json"),
+                },
+                None => None,
             };
 
-            let mut tags_out: String = String::new();
-            match tags {
-                Some(tags) => {
-                    for tag in tags {
-                        tags_out += &(format!("#{} ", tag));
-                    }
-                }
-                None => {}
+            // 標準入力を読めなかった場合にメモを作らないよう、先に本文を読む
+            let body = if stdin {
+                let mut body = String::new();
+                std::io::stdin().read_to_string(&mut body).context("can't read the body from stdin")?;
+                Some(body).filter(|x| !x.trim().is_empty())
+            } else {
+                body
             };
 
-            let contents = format!(" <!---\n tags: {}\n --->\n", tags_out);
-            match file.write_all(contents.as_bytes()) {
-                Err(why) => panic!("Error:{}", why),
-                Ok(_) => println!("finished"),
+            let tags = tags.unwrap_or_default();
+            let mut contents = template::create_memo_contents(template.as_deref(), &title, &tags, &Local::now());
+            if let Some(body) = body {
+                contents += &body;
+                if !body.ends_with('\n') {
+                    contents.push('\n');
+                }
             }
 
-            launch_file(&(path.to_str().unwrap().to_string() + &filename), setting.get_app_using_openmemo()).unwrap();
+            // 同じ名前のメモがあれば上書きせず、`_2`などを付ける
+            let path_memo = path::get_unique_path(path.join(&filename));
+            let mut file = fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path_memo)
+                .with_context(|| format!("can't create {}", path_memo.display()))?;
+            file.write_all(contents.as_bytes())?;
+            println!("finished");
+            let filename = path_memo.file_name().unwrap().to_string_lossy();
+            git::auto_commit(&setting, &path_memo, &format!("add {}", filename));
+
+            if !no_open && !stdin {
+                launch_file(path_memo.to_str().unwrap(), setting.get_app_using_openmemo()).unwrap();
+            }
             Ok(())
         }
         Sub::SetPath { path: _ } => {
@@ -132,14 +390,350 @@ fn main() -> Result<()> {
             launch_file("E:/memo/todo.md", setting.get_app_using_openmemo()).unwrap();
             Ok(())
         }
+        Sub::Today { date } => {
+            let date = journal::parse_journal_date(&date, Local::now().date_naive())?;
+            let dir_journal = match setting.get_journal_path() {
+                Some(dir_journal) => dir_journal,
+                None => bail!("path_journal isn't set. Please add path_journal to setting.json"),
+            };
+            let template = match (setting.get_template_path(), setting.get_journal_template()) {
+                (Some(dir_template), Some(name)) => Some(template::read_template(dir_template, name)?),
+                _ => None,
+            };
+            let path = journal::create_journal_if_not_exist(dir_journal, date, template.as_deref())?;
+            launch_file(path.to_str().unwrap(), setting.get_app_using_openmemo()).unwrap();
+            Ok(())
+        }
+        Sub::Journal { days } => {
+            if days == 0 {
+                bail!("--days must be 1 or more");
+            }
+            let dir_journal = match setting.get_journal_path() {
+                Some(dir_journal) => dir_journal,
+                None => bail!("path_journal isn't set. Please add path_journal to setting.json"),
+            };
+            let lst_memo_journal = memo::create_memo_list(&vec![dir_journal.clone()]);
+            let lst_journal = journal::filter_journal(&lst_memo_journal, Local::now().date_naive(), days);
+            list::print_memo_list(&lst_journal, "plain")?;
+            Ok(())
+        }
+        Sub::Tasks { tags, all, overdue, due_within, sub } => {
+            let tasks = task::create_task_list(&lst_memo);
+            match sub {
+                Some(SubTasks::Done { id }) => {
+                    let task = task::find_task(&tasks, &id)?;
+                    history::save_snapshot(Path::new(task.get_path()), Local::now().naive_local(), history::Retention::from_setting(&setting))?;
+                    task::toggle_task(task)?;
+                    git::auto_commit(&setting, Path::new(task.get_path()), &format!("toggle task {}", task.get_id()));
+                    println!("{} [{}] {}", task.get_id(), if task.is_done() { " " } else { "x" }, task.get_text());
+                }
+                None => {
+                    let today = Local::now().date_naive();
+                    let due_within = match due_within {
+                        Some(days) => Some(task::parse_days(&days)?),
+                        None => None,
+                    };
+                    let mut tasks: Vec<task::Task> = tasks
+                        .into_iter()
+                        .filter(|task| all || !task.is_done())
+                        .filter(|task| match &tags {
+                            Some(tags) => memo::is_include_these_tags(tags, task.get_tags()),
+                            None => true,
+                        })
+                        // --overdueと--due-withinの両方を指定した場合はどちらかに当てはまるもの
+                        .filter(|task| {
+                            if !overdue && due_within.is_none() {
+                                return true;
+                            }
+                            (overdue && task.is_overdue(today))
+                                || due_within.is_some_and(|days| task.is_due_within(today, days))
+                        })
+                        .collect();
+                    if overdue || due_within.is_some() {
+                        task::sort_by_due(&mut tasks);
+                    }
+                    task::print_task_list(&tasks);
+                }
+            }
+            Ok(())
+        }
+        Sub::Check {} => {
+            let broken = check::check_memos(&lst_memo, setting.get_common_mark_paths());
+            for link in &broken {
+                println!("{}", link);
+            }
+            if !broken.is_empty() {
+                bail!("{} broken links found", broken.len());
+            }
+            Ok(())
+        }
+        Sub::Graph { tags, format } => {
+            let lst_memo = match tags {
+                Some(tags) => memo::filter_memo_by_tags(&lst_memo, &tags),
+                None => lst_memo,
+            };
+            let graph = graph::create_graph(&lst_memo);
+            match format.as_str() {
+                "json" => println!("{}", serde_json::to_string_pretty(&graph)?),
+                _ => print!("{}", graph::to_dot(&graph)),
+            }
+            Ok(())
+        }
+        Sub::Export { sub: SubExport::Html { out_dir } } => {
+            let out_dir = dir_current.join(out_dir);
+            let exporter = export::Exporter::new(&lst_memo).with_common_mark_dirs(setting.get_common_mark_paths());
+            let count = exporter.export(&out_dir)?;
+            println!("exported {} memos to {}", count, out_dir.display());
+            Ok(())
+        }
+        Sub::Import { from, path, to } => {
+            let dir_memo = match to {
+                Some(to) => dir_current.join(to),
+                None => match setting.get_memo_path().first() {
+                    Some(dir_memo) => PathBuf::from(dir_memo),
+                    None => bail!("path_memo isn't set. Please add path_memo to setting.json"),
+                },
+            };
+            fs::create_dir_all(&dir_memo).with_context(|| format!("can't create {}", dir_memo.display()))?;
+            let path = dir_current.join(path);
+            let mut notes = match from.as_str() {
+                "enex" => import::read_enex_notes(&memo::read_text(&path)?)?,
+                _ => import::read_markdown_notes(&path)?,
+            };
+            // 先に全てのメモのパスを決めてから、ノート同士のリンクを書き換える
+            let paths = import::assign_note_paths(&notes, &dir_memo);
+            import::rewrite_note_links(&mut notes, &paths);
+            let mut path_last: Option<PathBuf> = None;
+            let mut count = 0;
+            let mut result = Ok(());
+            for (note, path) in notes.iter().zip(paths) {
+                // 途中で失敗しても、それまでに書いたメモはコミットする
+                if let Err(e) = import::write_note(note, &path) {
+                    result = Err(e);
+                    break;
+                }
+                println!("{}", path.display());
+                path_last = Some(path);
+                count += 1;
+            }
+            if let Some(path) = path_last {
+                git::auto_commit(&setting, &path, &format!("import {} notes from {}", count, from));
+            }
+            println!("imported {} notes", count);
+            result
+        }
+        Sub::Log { memo } => {
+            let memo = match memo::find_memo(&memo, &lst_memo) {
+                Some(memo) => memo,
+                None => bail!("memo {} isn't found", memo),
+            };
+            for revision in git::log(Path::new(memo.get_path()))? {
+                println!("{} {} {}", revision.hash, revision.date, revision.subject);
+            }
+            Ok(())
+        }
+        Sub::Show { spec } => {
+            let (memo, rev) = git::split_revision(&spec)?;
+            let memo = match memo::find_memo(memo, &lst_memo) {
+                Some(memo) => memo,
+                None => bail!("memo {} isn't found", memo),
+            };
+            print!("{}", git::show(Path::new(memo.get_path()), rev)?);
+            Ok(())
+        }
+        Sub::History { memo } => {
+            let memo = match memo::find_memo(&memo, &lst_memo) {
+                Some(memo) => memo,
+                None => bail!("memo {} isn't found", memo),
+            };
+            for snapshot in history::list_snapshots(Path::new(memo.get_path())) {
+                println!("{}\t{}", snapshot.get_time().format("%Y-%m-%d %H:%M:%S"), snapshot.get_path().display());
+            }
+            Ok(())
+        }
+        Sub::Restore { memo, at } => {
+            let memo = match memo::find_memo(&memo, &lst_memo) {
+                Some(memo) => memo,
+                None => bail!("memo {} isn't found", memo),
+            };
+            let path = Path::new(memo.get_path());
+            let snapshots = history::list_snapshots(path);
+            let snapshot = match history::find_snapshot_at(&snapshots, history::parse_time(&at)?) {
+                Some(snapshot) => snapshot,
+                None => bail!("no snapshot of {} at {}", memo.get_path(), at),
+            };
+            history::restore(path, snapshot, Local::now().naive_local(), history::Retention::from_setting(&setting))?;
+            let time = snapshot.get_time().format("%Y-%m-%d %H:%M:%S");
+            println!("restored {} from {}", memo.get_path(), time);
+            git::auto_commit(&setting, path, &format!("restore {} from {}", memo.get_path(), time));
+            Ok(())
+        }
+        Sub::Archive { memo, tags, yes } => {
+            for memo in select_memos(&lst_memo, memo, tags, yes)? {
+                let path = Path::new(memo.get_path());
+                let dest = archive::archive_memo(path, setting.get_archive_path())?;
+                println!("archived {}", dest.display());
+                git::auto_commit(&setting, path, &format!("archive {}", memo.get_path()));
+            }
+            Ok(())
+        }
+        Sub::Rm { memo, tags, yes } => {
+            for memo in select_memos(&lst_memo, memo, tags, yes)? {
+                let path = Path::new(memo.get_path());
+                let dest = archive::trash_memo(path, Local::now().naive_local())?;
+                println!("moved to trash {}", dest.display());
+                git::auto_commit(&setting, path, &format!("trash {}", memo.get_path()));
+            }
+            Ok(())
+        }
+        Sub::Mv { memo, dest } => {
+            let memo = match memo::find_memo(&memo, &lst_memo) {
+                Some(memo) => memo,
+                None => bail!("memo {} isn't found", memo),
+            };
+            let path = Path::new(memo.get_path());
+            let dir_dest = dir_current.join(&dest);
+            let path_new = if dir_dest.is_dir() {
+                dir_dest.join(path.file_name().unwrap_or_default())
+            } else {
+                mv::get_renamed_path(path, &dest)?
+            };
+            let lst_rewritten = mv::move_memo_and_links(memo, &path_new, &lst_memo, history::Retention::from_setting(&setting))?;
+            println!("moved {} to {}", memo.get_path(), path_new.display());
+            for path in &lst_rewritten {
+                println!("updated links in {}", path.display());
+            }
+            let message = format!("move {} to {}", memo.get_path(), path_new.display());
+            git::auto_commit(&setting, path, &message);
+            git::auto_commit(&setting, &path_new, &message);
+            Ok(())
+        }
+        Sub::Trash { sub } => {
+            let items = archive::list_trash(setting.get_memo_path());
+            match sub {
+                None => {
+                    for item in &items {
+                        println!("{}\t{}\t{}", item.get_time().format("%Y-%m-%d %H:%M:%S"), item.get_name(), item.get_path().display());
+                    }
+                }
+                Some(SubTrash::Restore { memo }) => {
+                    let name = memo.to_lowercase();
+                    let item = items.iter().find(|item| {
+                        let memo = memo::Memo::new(item.get_name().clone(), Vec::new());
+                        item.get_name().to_lowercase() == name || memo.get_title().to_lowercase() == name
+                    });
+                    let item = match item {
+                        Some(item) => item,
+                        None => bail!("memo {} isn't in the trash", memo),
+                    };
+                    let dest = archive::restore_from_trash(item)?;
+                    println!("restored {}", dest.display());
+                    git::auto_commit(&setting, &dest, &format!("restore {} from trash", item.get_name()));
+                }
+                Some(SubTrash::Empty { yes }) => {
+                    if !items.is_empty() && !yes {
+                        for item in &items {
+                            println!("{}", item.get_name());
+                        }
+                        bail!("{} memos will be deleted permanently. Please add --yes to continue", items.len());
+                    }
+                    for item in &items {
+                        archive::delete_from_trash(item)?;
+                    }
+                    println!("deleted {} memos", items.len());
+                }
+            }
+            Ok(())
+        }
+        Sub::Attach { memo, files, shared } => {
+            let memo = match memo::find_memo(&memo, &lst_memo) {
+                Some(memo) => memo,
+                None => bail!("memo {} isn't found", memo),
+            };
+            let path = Path::new(memo.get_path());
+            let files: Vec<PathBuf> = files.iter().map(|file| dir_current.join(file)).collect();
+            let retention = history::Retention::from_setting(&setting);
+            for dest in attach::attach_files(path, &files, shared, retention)? {
+                println!("attached {}", dest.display());
+            }
+            git::auto_commit(&setting, path, &format!("attach files to {}", memo.get_path()));
+            Ok(())
+        }
+        Sub::Dupes { threshold, archive } => {
+            if !(0.0..=1.0).contains(&threshold) {
+                bail!("threshold must be between 0.0 and 1.0: {}", threshold);
+            }
+            let groups = dupes::find_dupes_in_memos(&lst_memo, threshold);
+            if groups.is_empty() {
+                println!("no duplicated memos");
+                return Ok(());
+            }
+            for (i, group) in groups.iter().enumerate() {
+                println!("group {} ({})", i + 1, if group.is_exact { "exact" } else { "similar" });
+                for (n, (path, score)) in group.memos.iter().enumerate() {
+                    let mark = if n == 0 { "keep".to_string() } else { format!("{:.2}", score) };
+                    println!("  {:>5} {}", mark, path);
+                }
+            }
+            let lst_extra: Vec<&String> = groups.iter().flat_map(|group| group.memos.iter().skip(1).map(|(path, _)| path)).collect();
+            let is_archive = archive || {
+                if !std::io::stdin().is_terminal() {
+                    println!("run with --archive to archive {} duplicated memos", lst_extra.len());
+                    return Ok(());
+                }
+                print!("archive {} duplicated memos? [y/N] ", lst_extra.len());
+                std::io::stdout().flush()?;
+                let mut answer = String::new();
+                std::io::stdin().read_line(&mut answer)?;
+                answer.trim().eq_ignore_ascii_case("y")
+            };
+            if !is_archive {
+                return Ok(());
+            }
+            for path in lst_extra {
+                let path = Path::new(path);
+                let dest = archive::archive_memo(path, setting.get_archive_path())?;
+                println!("archived {}", dest.display());
+                git::auto_commit(&setting, path, &format!("archive duplicated {}", path.display()));
+            }
+            Ok(())
+        }
+        Sub::Tags { format, co_occurrence } => {
+            let stats = tag::create_tag_stats(&lst_memo, co_occurrence.unwrap_or(0));
+            match format.as_str() {
+                "json" => println!("{}", serde_json::to_string_pretty(&stats)?),
+                _ => tag::print_tag_stats_table(&stats),
+            }
+            Ok(())
+        }
         Sub::GUI {} => {
             let app = gui::TemplateApp::default();
-            let native_options = eframe::NativeOptions::default();
+            let native_options = eframe::NativeOptions {
+                drag_and_drop_support: true,
+                ..Default::default()
+            };
             eframe::run_native(Box::new(app), native_options); 
         }
     }
 }
 
+/// `archive`や`rm`の対象のメモを、メモの名前かタグで選びます。複数のメモを選んだ場合は`--yes`が必要です。
+fn select_memos(lst_memo: &[memo::Memo], memo: Option<String>, tags: Option<Vec<String>>, yes: bool) -> Result<Vec<memo::Memo>> {
+    let lst_selected = match (memo, tags) {
+        (Some(name), None) => match memo::find_memo(&name, lst_memo) {
+            Some(memo) => vec![memo.clone()],
+            None => bail!("memo {} isn't found", name),
+        },
+        (None, Some(tags)) => memo::filter_memo_by_tags(lst_memo, &tags),
+        _ => bail!("please specify either a memo or --tags"),
+    };
+    if lst_selected.len() > 1 && !yes {
+        list::print_memo_list(&lst_selected, "plain")?;
+        bail!("{} memos are selected. Please add --yes to continue", lst_selected.len());
+    }
+    Ok(lst_selected)
+}
+
 fn launch_file(path: &str, app_using_openmemo: &String) -> winrt::Result<()> {
     //assert!(env::set_current_dir(&Path::new("C:/Users/user/Documents/memo")).is_ok());
     let path = path.replace("/", "\\").to_string();
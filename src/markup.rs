@@ -0,0 +1,35 @@
+//! メモの書き方(EasyMark、CommonMark)のパーサー。GUI、TUI、リンクのチェック、HTMLへの書き出しで使います。
+
+mod common_mark_parser;
+pub mod easy_mark_parser;
+
+pub use easy_mark_parser::{ColumnAlign, Item, Parser, Style, Table};
+
+/// メモの書き方
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Syntax {
+    EasyMark,
+    CommonMark,
+}
+
+/// `s`を`syntax`として読みます。
+pub fn parse(s: &str, syntax: Syntax) -> Vec<Item<'_>> {
+    match syntax {
+        Syntax::EasyMark => Parser::new(s).collect(),
+        Syntax::CommonMark => common_mark_parser::parse(s),
+    }
+}
+
+/// [`parse`]と同じですが、各[`Item::Table`]の後に表のセルの中の要素を続けます。
+pub fn items_with_table_cells(s: &str, syntax: Syntax) -> Vec<Item<'_>> {
+    parse(s, syntax)
+        .into_iter()
+        .flat_map(|item| {
+            let cells: Vec<Item> = match &item {
+                Item::Table(table) => table.cells().into_iter().flat_map(|cell| table.parse_cell(cell)).collect(),
+                _ => Vec::new(),
+            };
+            std::iter::once(item).chain(cells)
+        })
+        .collect()
+}
@@ -0,0 +1,417 @@
+//! A CommonMark parser producing the same [`Item`]s as the `EasyMark` parser.
+//!
+//! Parsing is done by `pulldown-cmark`. Its events are turned into items borrowing from the source,
+//! so that the viewer and the exporter work the same for both languages.
+
+use super::easy_mark_parser::{Item, Style, Table};
+use super::Syntax;
+use crate::link;
+use pulldown_cmark::{CodeBlockKind, CowStr, Event, Options, Parser, Tag};
+use std::borrow::Cow;
+use std::ops::Range;
+
+/// Indentation of a nested list, in spaces.
+const LIST_INDENT: usize = 4;
+
+struct Converter<'a> {
+    source: &'a str,
+    items: Vec<Item<'a>>,
+    style: Style,
+    quote_depth: usize,
+    list_depth: usize,
+    /// Index of the bullet or number of the list item being read, to be replaced by a task marker
+    item_marker: Option<usize>,
+    /// Paragraphs, headings and table cells, where HTML is inline.
+    inline_depth: usize,
+    /// Destination of the link being read
+    link: Option<Cow<'a, str>>,
+    /// Destination and alt text of the image being read
+    image: Option<(Cow<'a, str>, Option<Range<usize>>)>,
+    /// Language and code of the code block being read
+    code_block: Option<(&'a str, Option<Range<usize>>)>,
+    /// Lines of the HTML block being read
+    html_block: Option<Range<usize>>,
+    /// Events inside a table are skipped, the table is parsed from its source.
+    is_in_table: bool,
+}
+
+/// Joins two ranges of the source.
+fn join(range: Option<Range<usize>>, other: Range<usize>) -> Option<Range<usize>> {
+    Some(match range {
+        Some(range) => range.start.min(other.start)..range.end.max(other.end),
+        None => other,
+    })
+}
+
+impl<'a> Converter<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            items: Vec::new(),
+            style: Style::default(),
+            quote_depth: 0,
+            list_depth: 0,
+            item_marker: None,
+            inline_depth: 0,
+            link: None,
+            image: None,
+            code_block: None,
+            html_block: None,
+            is_in_table: false,
+        }
+    }
+
+    /// The text of an event, borrowing from the source if it is there as is.
+    /// Decoded text (e.g. `&` for `&amp;`, or a link destination with escapes) is owned.
+    fn text(text: CowStr<'a>) -> Cow<'a, str> {
+        match text {
+            CowStr::Borrowed(text) => Cow::Borrowed(text),
+            text => Cow::Owned(text.to_string()),
+        }
+    }
+
+    /// Where `text` starts in the source, if it is a slice of the source.
+    fn offset_of(&self, text: &str) -> Option<usize> {
+        let start = self.source.as_ptr() as usize;
+        let ptr = text.as_ptr() as usize;
+        Some(ptr - start).filter(|_| ptr >= start && ptr + text.len() <= start + self.source.len())
+    }
+
+    fn line_of(&self, offset: usize) -> usize {
+        self.source[..offset].matches('\n').count() + 1
+    }
+
+    fn is_line_start(&self) -> bool {
+        matches!(self.items.last(), None | Some(Item::Newline))
+    }
+
+    /// Quote marks and indentation at the start of a line. The list marker itself is one level less indented.
+    fn line_prefix(&mut self, is_list_marker: bool) {
+        for _ in 0..self.quote_depth {
+            self.items.push(Item::QuoteIndent);
+        }
+        let depth = if is_list_marker { self.list_depth.saturating_sub(1) } else { self.list_depth };
+        if depth > 0 {
+            self.items.push(Item::Indentation(depth * LIST_INDENT));
+        }
+    }
+
+    fn start_block(&mut self) {
+        if !self.is_line_start() {
+            self.items.push(Item::Newline);
+        }
+        self.line_prefix(false);
+    }
+
+    /// Ends the line, and leaves a blank line between blocks outside lists.
+    fn end_block(&mut self) {
+        if !self.is_line_start() {
+            self.items.push(Item::Newline);
+        }
+        if self.list_depth == 0 {
+            self.items.push(Item::Newline);
+        }
+    }
+
+    fn text_style(&self) -> Style {
+        Style {
+            quoted: self.quote_depth > 0,
+            ..self.style
+        }
+    }
+
+    /// Pushes text, joining it with the previous text if they are next to each other in the source.
+    /// Joined text keeps `[[wiki links]]` in one piece.
+    fn push_text(&mut self, style: Style, text: Cow<'a, str>) {
+        if let (Some(Item::Text(style_last, Cow::Borrowed(last))), Cow::Borrowed(text)) = (self.items.last(), &text) {
+            if let (true, Some(start), Some(end)) = (*style_last == style, self.offset_of(last), self.offset_of(text)) {
+                if start + last.len() == end {
+                    let joined = &self.source[start..end + text.len()];
+                    *self.items.last_mut().unwrap() = Item::Text(style, joined.into());
+                    return;
+                }
+            }
+        }
+        self.items.push(Item::Text(style, text));
+    }
+
+    fn flush_html_block(&mut self) {
+        if let Some(range) = self.html_block.take() {
+            let html = self.source[range].trim_end();
+            // `<!--- tags: ... --->`のようなコメントは表示しない
+            if !html.starts_with("<!--") {
+                self.start_block();
+                self.items.push(Item::CodeBlock("html", html));
+                self.items.push(Item::Newline);
+                self.end_block();
+            }
+        }
+    }
+
+    fn start(&mut self, tag: Tag<'a>, range: Range<usize>) {
+        match tag {
+            Tag::Paragraph => {
+                self.inline_depth += 1;
+                if self.is_line_start() {
+                    self.line_prefix(false);
+                }
+            }
+            Tag::Heading(level, ..) => {
+                self.inline_depth += 1;
+                self.start_block();
+                self.style.heading = Some(level as u8);
+            }
+            Tag::BlockQuote => self.quote_depth += 1,
+            Tag::CodeBlock(kind) => {
+                let language = match kind {
+                    CodeBlockKind::Fenced(CowStr::Borrowed(language)) => language,
+                    _ => "",
+                };
+                self.code_block = Some((language, None));
+            }
+            Tag::List(_) => self.list_depth += 1,
+            Tag::Item => {
+                if !self.is_line_start() {
+                    self.items.push(Item::Newline);
+                }
+                self.line_prefix(true);
+                self.item_marker = Some(self.items.len());
+                let marker = self.source[range].trim_start();
+                let len = marker.find(|c: char| !c.is_ascii_digit()).unwrap_or(marker.len());
+                if len > 0 {
+                    self.items.push(Item::NumberedPoint(&marker[..len]));
+                } else {
+                    self.items.push(Item::BulletPoint);
+                }
+            }
+            Tag::Table(_) => {
+                self.start_block();
+                let source = self.source[range].trim_end();
+                self.items.push(Item::Table(Table::new(source, Syntax::CommonMark)));
+                self.is_in_table = true;
+            }
+            Tag::Emphasis => self.style.italics = true,
+            Tag::Strong => self.style.strong = true,
+            Tag::Strikethrough => self.style.strikethrough = true,
+            Tag::Link(_, url, _) => self.link = Some(Self::text(url)),
+            Tag::Image(_, url, _) => self.image = Some((Self::text(url), None)),
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: Tag<'a>) {
+        match tag {
+            Tag::Paragraph => {
+                self.inline_depth -= 1;
+                self.end_block();
+            }
+            Tag::Heading(..) => {
+                self.inline_depth -= 1;
+                self.style.heading = None;
+                self.end_block();
+            }
+            Tag::BlockQuote => self.quote_depth -= 1,
+            Tag::CodeBlock(_) => {
+                if let Some((language, range)) = self.code_block.take() {
+                    let code = range.map_or("", |range| self.source[range].trim_end_matches(&['\r', '\n'][..]));
+                    self.start_block();
+                    self.items.push(Item::CodeBlock(language, code));
+                    self.items.push(Item::Newline);
+                    self.end_block();
+                }
+            }
+            Tag::List(_) => {
+                self.list_depth -= 1;
+                if self.list_depth == 0 {
+                    self.end_block();
+                }
+            }
+            Tag::Item if !self.is_line_start() => self.items.push(Item::Newline),
+            Tag::Table(_) => {
+                self.is_in_table = false;
+                self.end_block();
+            }
+            Tag::Emphasis => self.style.italics = false,
+            Tag::Strong => self.style.strong = false,
+            Tag::Strikethrough => self.style.strikethrough = false,
+            Tag::Link(..) => self.link = None,
+            Tag::Image(..) => {
+                if let Some((url, range)) = self.image.take() {
+                    let alt = range.map_or("", |range| &self.source[range]);
+                    self.items.push(Item::Image(alt.into(), url));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn event(&mut self, event: Event<'a>, range: Range<usize>) {
+        if !matches!(event, Event::Html(_)) || self.inline_depth > 0 {
+            self.flush_html_block();
+        }
+        if self.is_in_table && !matches!(event, Event::End(Tag::Table(_))) {
+            return;
+        }
+        match event {
+            Event::Start(tag) => self.start(tag, range),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) => {
+                let text = Self::text(text);
+                let range = match &text {
+                    Cow::Borrowed(text) => self.offset_of(text).map_or(range, |start| start..start + text.len()),
+                    Cow::Owned(_) => range,
+                };
+                if let Some((_, code)) = &mut self.code_block {
+                    *code = join(code.take(), range);
+                } else if let Some((_, alt)) = &mut self.image {
+                    *alt = join(alt.take(), range);
+                } else if let Some(url) = &self.link {
+                    self.items.push(Item::Hyperlink(self.text_style(), text, url.clone()));
+                } else {
+                    self.push_text(self.text_style(), text);
+                }
+            }
+            Event::Code(code) => {
+                let code = Self::text(code);
+                let style = Style { code: true, ..self.text_style() };
+                match &self.link {
+                    Some(url) => self.items.push(Item::Hyperlink(style, code, url.clone())),
+                    None => self.items.push(Item::Text(style, code)),
+                }
+            }
+            Event::Html(html) => {
+                if self.inline_depth == 0 {
+                    self.html_block = join(self.html_block.take(), range);
+                } else if matches!(html.trim().to_ascii_lowercase().as_str(), "<br>" | "<br/>" | "<br />") {
+                    self.items.push(Item::Newline);
+                    self.line_prefix(false);
+                }
+            }
+            Event::SoftBreak | Event::HardBreak => {
+                self.items.push(Item::Newline);
+                self.line_prefix(false);
+            }
+            Event::Rule => {
+                if !self.is_line_start() {
+                    self.items.push(Item::Newline);
+                }
+                self.items.push(Item::Separator);
+            }
+            Event::TaskListMarker(is_done) => {
+                let line = Some(self.line_of(range.start)).filter(|_| self.quote_depth == 0);
+                if let Some(marker) = self.item_marker.take() {
+                    self.items[marker] = Item::Task(is_done, line);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Splits `[[target]]` and `[[target|label]]` out of text, by the same rules as [`link::parse_wiki_link`].
+fn split_wiki_links<'a>(style: Style, text: Cow<'a, str>, items: &mut Vec<Item<'a>>) {
+    match text {
+        Cow::Borrowed(text) => split_wiki_links_with(style, text, Cow::Borrowed, items),
+        Cow::Owned(text) => split_wiki_links_with(style, &text, |s| Cow::Owned(s.to_string()), items),
+    }
+}
+
+fn split_wiki_links_with<'s, 'a>(
+    style: Style,
+    text: &'s str,
+    to_cow: impl Fn(&'s str) -> Cow<'a, str>,
+    items: &mut Vec<Item<'a>>,
+) {
+    // The text not yet pushed starts at `pos`, and `[[` is searched from `search`.
+    let (mut pos, mut search) = (0, 0);
+    while let Some(found) = text[search..].find("[[") {
+        let start = search + found;
+        match link::parse_wiki_link(&text[start..]) {
+            Some((target, label, len)) => {
+                if start > pos {
+                    items.push(Item::Text(style, to_cow(&text[pos..start])));
+                }
+                items.push(Item::WikiLink(style, to_cow(label), to_cow(target)));
+                pos = start + len;
+                search = pos;
+            }
+            None => search = start + 2,
+        }
+    }
+    if pos < text.len() {
+        items.push(Item::Text(style, to_cow(&text[pos..])));
+    }
+}
+
+/// Parses CommonMark with tables, strikethrough and task lists.
+pub fn parse(source: &str) -> Vec<Item<'_>> {
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+    let mut converter = Converter::new(source);
+    for (event, range) in Parser::new_ext(source, options).into_offset_iter() {
+        converter.event(event, range);
+    }
+    converter.flush_html_block();
+
+    let mut items = Vec::new();
+    for item in converter.items {
+        match item {
+            Item::Text(style, text) if !style.code => split_wiki_links(style, text, &mut items),
+            item => items.push(item),
+        }
+    }
+    while items.last() == Some(&Item::Newline) {
+        items.pop();
+    }
+    items
+}
+
+#[test]
+fn test_common_mark_parser() {
+    let source = " <!---\n tags: #memo \n --->\nTitle\n=====\n\n**bold** _em_ /usr/bin [[211003_design|design]]\n\n- [x] done\n  1. see [doc][ref]\n\n[ref]: doc.md\n";
+    let strong = Style { strong: true, ..Default::default() };
+    let italics = Style { italics: true, ..Default::default() };
+    assert_eq!(
+        parse(source),
+        vec![
+            Item::Text(Style { heading: Some(1), ..Default::default() }, "Title".into()),
+            Item::Newline,
+            Item::Newline,
+            Item::Text(strong, "bold".into()),
+            Item::Text(Style::default(), " ".into()),
+            Item::Text(italics, "em".into()),
+            Item::Text(Style::default(), " /usr/bin ".into()),
+            Item::WikiLink(Style::default(), "design".into(), "211003_design".into()),
+            Item::Newline,
+            Item::Newline,
+            Item::Task(true, Some(9)),
+            Item::Text(Style::default(), "done".into()),
+            Item::Newline,
+            Item::Indentation(LIST_INDENT),
+            Item::NumberedPoint("1"),
+            Item::Text(Style::default(), "see ".into()),
+            Item::Hyperlink(Style::default(), "doc".into(), "doc.md".into()),
+        ]
+    );
+}
+
+#[test]
+fn test_common_mark_parser_decoded() {
+    let source = "- a\n\n1. [ ] b\n\nR&amp;D [spec](a&amp;b\\_c.md)\n";
+    assert_eq!(
+        parse(source),
+        vec![
+            Item::BulletPoint,
+            Item::Text(Style::default(), "a".into()),
+            Item::Newline,
+            Item::Newline,
+            Item::Task(false, Some(3)),
+            Item::Text(Style::default(), "b".into()),
+            Item::Newline,
+            Item::Newline,
+            Item::Text(Style::default(), "R".into()),
+            Item::Text(Style::default(), "&".into()),
+            Item::Text(Style::default(), "D ".into()),
+            Item::Hyperlink(Style::default(), "spec".into(), "a&b_c.md".into()),
+        ]
+    );
+}
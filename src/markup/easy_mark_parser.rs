@@ -10,33 +10,165 @@
 //! 2. easy to learn
 //! 3. similar to markdown
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+use super::{parse, Syntax};
+use crate::link;
+use std::borrow::Cow;
+
+/// Text is borrowed from the source, or owned when the parser had to decode it (e.g. `&amp;` in CommonMark).
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Item<'a> {
     /// `\n`
     // TODO: add Style here so empty heading still uses up the right amount of space.
     Newline,
     ///
-    Text(Style, &'a str),
+    Text(Style, Cow<'a, str>),
     /// title, url
-    Hyperlink(Style, &'a str, &'a str),
+    Hyperlink(Style, Cow<'a, str>, Cow<'a, str>),
+    /// `![alt](url)`: alt, url
+    Image(Cow<'a, str>, Cow<'a, str>),
+    /// `[[target]]` or `[[target|label]]`: label, target (title or file name of a memo)
+    WikiLink(Style, Cow<'a, str>, Cow<'a, str>),
     /// leading space before e.g. a [`Self::BulletPoint`].
     Indentation(usize),
     /// >
     QuoteIndent,
     /// - a point well made.
     BulletPoint,
+    /// `- [ ] ` or `- [x] `: is done, line number (1-based) in the source.
+    /// The line is `None` inside quotes and table cells, where the task can't be toggled.
+    Task(bool, Option<usize>),
     /// 1. numbered list. The string is the number(s).
     NumberedPoint(&'a str),
     /// ---
     Separator,
     /// language, code
     CodeBlock(&'a str, &'a str),
+    /// | pipe | table |
+    Table(Table<'a>),
+}
+
+/// Alignment of a table column, from the `|:---|:---:|---:|` row.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ColumnAlign {
+    None,
+    Left,
+    Center,
+    Right,
+}
+
+/// A pipe table: a header row, an alignment row and the body rows.
+///
+/// Cells are kept as source text so that they can be parsed for inline styles.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Table<'a> {
+    /// The lines of the table, without the trailing newline.
+    source: &'a str,
+    /// The syntax of the cells
+    syntax: Syntax,
+}
+
+impl<'a> Table<'a> {
+    pub fn new(source: &'a str, syntax: Syntax) -> Self {
+        Self { source, syntax }
+    }
+
+    /// The items of a cell, in the same syntax as the table.
+    /// Tasks in a cell have no line, as their line numbers are relative to the cell.
+    pub fn parse_cell(&self, cell: &'a str) -> Vec<Item<'a>> {
+        let mut items: Vec<Item<'a>> = parse(cell, self.syntax)
+            .into_iter()
+            .map(|item| match item {
+                Item::Task(is_done, _) => Item::Task(is_done, None),
+                item => item,
+            })
+            .collect();
+        while items.last() == Some(&Item::Newline) {
+            items.pop();
+        }
+        items
+    }
+
+    pub fn header(&self) -> Vec<&'a str> {
+        split_cells(self.source.lines().next().unwrap_or(""))
+    }
+
+    pub fn alignments(&self) -> Vec<ColumnAlign> {
+        let n_column = self.header().len();
+        let mut alignments: Vec<ColumnAlign> = split_cells(self.source.lines().nth(1).unwrap_or(""))
+            .iter()
+            .map(|cell| match (cell.starts_with(':'), cell.ends_with(':')) {
+                (true, true) => ColumnAlign::Center,
+                (true, false) => ColumnAlign::Left,
+                (false, true) => ColumnAlign::Right,
+                (false, false) => ColumnAlign::None,
+            })
+            .collect();
+        alignments.resize(n_column, ColumnAlign::None);
+        alignments
+    }
+
+    /// Body rows, padded or truncated to the number of header cells.
+    pub fn rows(&self) -> Vec<Vec<&'a str>> {
+        let n_column = self.header().len();
+        self.source
+            .lines()
+            .skip(2)
+            .map(|line| {
+                let mut cells = split_cells(line);
+                cells.resize(n_column, "");
+                cells
+            })
+            .collect()
+    }
+
+    /// All cells, header first.
+    pub fn cells(&self) -> Vec<&'a str> {
+        let mut cells = self.header();
+        cells.extend(self.rows().into_iter().flatten());
+        cells
+    }
+}
+
+/// `| a | b |` → `["a", "b"]`. `\|` doesn't split a cell.
+fn split_cells(line: &str) -> Vec<&str> {
+    let line = line.trim();
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = match line.strip_suffix('|') {
+        Some(rest) if !rest.ends_with('\\') => rest,
+        _ => line,
+    };
+    let mut cells = Vec::new();
+    let mut start = 0;
+    let mut is_escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '\\' => is_escaped = !is_escaped,
+            '|' if !is_escaped => {
+                cells.push(line[start..i].trim());
+                start = i + 1;
+            }
+            _ => is_escaped = false,
+        }
+    }
+    cells.push(line[start..].trim());
+    cells
+}
+
+/// `|---|:---:|` etc.
+fn is_alignment_row(line: &str) -> bool {
+    let cells = split_cells(line);
+    line.contains('-')
+        && cells.iter().all(|cell| {
+            let cell = cell.strip_prefix(':').unwrap_or(cell);
+            let cell = cell.strip_suffix(':').unwrap_or(cell);
+            !cell.is_empty() && cell.chars().all(|c| c == '-')
+        })
 }
 
 #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
 pub struct Style {
-    /// # heading (large text)
-    pub heading: bool,
+    /// # heading (large text): the number of `#`, 1 to 6
+    pub heading: Option<u8>,
     /// > quoted (slightly dimmer color or other font style)
     pub quoted: bool,
     /// `code` (monospace, some other color)
@@ -67,6 +199,8 @@ pub struct Style {
 ///
 /// ```
 pub struct Parser<'a> {
+    /// The whole input text
+    source: &'a str,
     /// The remainder of the input text
     s: &'a str,
     /// Are we at the start of a line?
@@ -78,12 +212,32 @@ pub struct Parser<'a> {
 impl<'a> Parser<'a> {
     pub fn new(s: &'a str) -> Self {
         Self {
+            source: s,
             s,
             start_of_line: true,
             style: Style::default(),
         }
     }
 
+    /// `- [ ] `, `- [x] ` etc.
+    fn task(&mut self) -> Option<Item<'a>> {
+        let rest = self.s.strip_prefix("- [")?;
+        let is_done = match rest.chars().next()? {
+            ' ' => false,
+            'x' | 'X' => true,
+            _ => return None,
+        };
+        let rest = rest[1..].strip_prefix(']')?;
+        if !(rest.is_empty() || rest.starts_with(&[' ', '\r', '\n'][..])) {
+            return None;
+        }
+        let line = self.source[..self.source.len() - self.s.len()].matches('\n').count() + 1;
+        let line = Some(line).filter(|_| !self.style.quoted);
+        self.s = rest.strip_prefix(' ').unwrap_or(rest);
+        self.start_of_line = false;
+        Some(Item::Task(is_done, line))
+    }
+
     /// `1. `, `42. ` etc.
     fn numbered_list(&mut self) -> Option<Item<'a>> {
         let bytes = self.s.as_bytes();
@@ -130,6 +284,21 @@ impl<'a> Parser<'a> {
         None
     }
 
+    /// A header row with `|`, followed by an alignment row. The table ends at a line without `|`.
+    fn table(&mut self) -> Option<Item<'a>> {
+        let mut lines = self.s.split_inclusive('\n');
+        let header = lines.next()?;
+        let alignment = lines.next()?;
+        if !header.contains('|') || !is_alignment_row(alignment) || split_cells(header).len() != split_cells(alignment).len() {
+            return None;
+        }
+        let len = header.len() + alignment.len() + lines.take_while(|line| line.contains('|')).map(str::len).sum::<usize>();
+        let source = self.s[..len].trim_end_matches(&['\r', '\n'][..]);
+        self.s = &self.s[len..];
+        self.start_of_line = true;
+        Some(Item::Table(Table::new(source, Syntax::EasyMark)))
+    }
+
     // `code`
     fn inline_code(&mut self) -> Option<Item<'a>> {
         if let Some(rest) = self.s.strip_prefix('`') {
@@ -138,13 +307,13 @@ impl<'a> Parser<'a> {
             self.style.code = true;
             let rest_of_line = &self.s[..self.s.find('\n').unwrap_or_else(|| self.s.len())];
             if let Some(end) = rest_of_line.find('`') {
-                let item = Item::Text(self.style, &self.s[..end]);
+                let item = Item::Text(self.style, self.s[..end].into());
                 self.s = &self.s[end + 1..];
                 self.style.code = false;
                 return Some(item);
             } else {
                 let end = rest_of_line.len();
-                let item = Item::Text(self.style, rest_of_line);
+                let item = Item::Text(self.style, rest_of_line.into());
                 self.s = &self.s[end..];
                 self.style.code = false;
                 return Some(item);
@@ -153,6 +322,28 @@ impl<'a> Parser<'a> {
         None
     }
 
+    /// `[[target]]` or `[[target|label]]`, read the same way as links are resolved and rewritten.
+    fn wiki_link(&mut self) -> Option<Item<'a>> {
+        let (target, label, len) = link::parse_wiki_link(self.s)?;
+        self.s = &self.s[len..];
+        self.start_of_line = false;
+        Some(Item::WikiLink(self.style, label.into(), target.into()))
+    }
+
+    /// `![alt](url)`
+    fn image(&mut self) -> Option<Item<'a>> {
+        let rest = self.s.strip_prefix("![")?;
+        let this_line = &rest[..rest.find('\n').unwrap_or(rest.len())];
+        let bracket_end = this_line.find(']')?;
+        let after = this_line[bracket_end + 1..].strip_prefix('(')?;
+        let parens_end = after.find(')')?;
+        let alt = &this_line[..bracket_end];
+        let url = &after[..parens_end];
+        self.s = &rest[bracket_end + 2 + parens_end + 1..];
+        self.start_of_line = false;
+        Some(Item::Image(alt.into(), url.trim().into()))
+    }
+
     /// `<url>` or `[link](url)`
     fn url(&mut self) -> Option<Item<'a>> {
         if self.s.starts_with('<') {
@@ -161,7 +352,7 @@ impl<'a> Parser<'a> {
                 let url = &self.s[1..url_end];
                 self.s = &self.s[url_end + 1..];
                 self.start_of_line = false;
-                return Some(Item::Hyperlink(self.style, url, url));
+                return Some(Item::Hyperlink(self.style, url.into(), url.into()));
             }
         }
 
@@ -176,7 +367,7 @@ impl<'a> Parser<'a> {
                         let url = &self.s[bracket_end + 2..parens_end];
                         self.s = &self.s[parens_end + 1..];
                         self.start_of_line = false;
-                        return Some(Item::Hyperlink(self.style, text, url));
+                        return Some(Item::Hyperlink(self.style, text.into(), url.into()));
                     }
                 }
             }
@@ -214,7 +405,7 @@ impl<'a> Iterator for Parser<'a> {
                 let text = &self.s[1..2];
                 self.s = &self.s[2..];
                 self.start_of_line = false;
-                return Some(Item::Text(self.style, text));
+                return Some(Item::Text(self.style, text.into()));
             }
 
             if self.start_of_line {
@@ -226,11 +417,12 @@ impl<'a> Iterator for Parser<'a> {
                     return Some(Item::Indentation(length));
                 }
 
-                // # Heading
-                if let Some(after) = self.s.strip_prefix("# ") {
-                    self.s = after;
+                // # Heading, ## Heading, ...
+                let level = self.s.len() - self.s.trim_start_matches('#').len();
+                if (1..=6).contains(&level) && self.s[level..].starts_with(' ') {
+                    self.s = &self.s[level + 1..];
                     self.start_of_line = false;
-                    self.style.heading = true;
+                    self.style.heading = Some(level as u8);
                     continue;
                 }
 
@@ -242,6 +434,11 @@ impl<'a> Iterator for Parser<'a> {
                     return Some(Item::QuoteIndent);
                 }
 
+                // - [ ] task
+                if let Some(item) = self.task() {
+                    return Some(item);
+                }
+
                 // - bullet point
                 if self.s.starts_with("- ") {
                     self.s = &self.s[2..];
@@ -266,6 +463,11 @@ impl<'a> Iterator for Parser<'a> {
                 if let Some(item) = self.code_block() {
                     return Some(item);
                 }
+
+                // | pipe | table |
+                if let Some(item) = self.table() {
+                    return Some(item);
+                }
             }
 
             // `code`
@@ -310,6 +512,16 @@ impl<'a> Iterator for Parser<'a> {
                 continue;
             }
 
+            // `[[target]]`
+            if let Some(item) = self.wiki_link() {
+                return Some(item);
+            }
+
+            // `![alt](url)`
+            if let Some(item) = self.image() {
+                return Some(item);
+            }
+
             // `<url>` or `[link](url)`
             if let Some(item) = self.url() {
                 return Some(item);
@@ -318,10 +530,10 @@ impl<'a> Iterator for Parser<'a> {
             // Swallow everything up to the next special character:
             let end = self
                 .s
-                .find(&['*', '`', '~', '_', '/', '$', '^', '\\', '<', '[', '\n'][..])
+                .find(&['*', '`', '~', '_', '/', '$', '^', '\\', '<', '[', '!', '\n'][..])
                 .map_or_else(|| self.s.len(), |special| special.max(1));
 
-            let item = Item::Text(self.style, &self.s[..end]);
+            let item = Item::Text(self.style, self.s[..end].into());
             self.s = &self.s[end..];
             self.start_of_line = false;
             return Some(item);
@@ -340,7 +552,7 @@ fn test_easy_mark_parser() {
                     strikethrough: true,
                     ..Default::default()
                 },
-                "strikethrough "
+                "strikethrough ".into()
             ),
             Item::Text(
                 Style {
@@ -348,8 +560,76 @@ fn test_easy_mark_parser() {
                     strikethrough: true,
                     ..Default::default()
                 },
-                "code"
+                "code".into()
             ),
         ]
     );
 }
+
+#[test]
+fn test_easy_mark_wiki_link() {
+    let items: Vec<_> = Parser::new("see [[211003_design|the design]] [[]]").collect();
+    assert_eq!(
+        items,
+        vec![
+            Item::Text(Style::default(), "see ".into()),
+            Item::WikiLink(Style::default(), "the design".into(), "211003_design".into()),
+            Item::Text(Style::default(), " ".into()),
+            Item::Text(Style::default(), "[".into()),
+            Item::Text(Style::default(), "[".into()),
+            Item::Text(Style::default(), "]]".into()),
+        ]
+    );
+}
+
+#[test]
+fn test_easy_mark_image() {
+    let items: Vec<_> = Parser::new("plan ![floor plan](assets/a/plan%201.png) done!").collect();
+    assert_eq!(
+        items,
+        vec![
+            Item::Text(Style::default(), "plan ".into()),
+            Item::Image("floor plan".into(), "assets/a/plan%201.png".into()),
+            Item::Text(Style::default(), " done".into()),
+            Item::Text(Style::default(), "!".into()),
+        ]
+    );
+}
+
+#[test]
+fn test_easy_mark_table() {
+    let items: Vec<_> = Parser::new("| name | *size* |\n|:--|--:|\n| a \\| b | 1 |\n| c |\n\nafter").collect();
+    let table = match items[0] {
+        Item::Table(table) => table,
+        _ => panic!("not a table: {:?}", items),
+    };
+    assert_eq!(table.header(), vec!["name", "*size*"]);
+    assert_eq!(table.alignments(), vec![ColumnAlign::Left, ColumnAlign::Right]);
+    assert_eq!(table.rows(), vec![vec!["a \\| b", "1"], vec!["c", ""]]);
+    assert_eq!(&items[1..], &[Item::Newline, Item::Text(Style::default(), "after".into())]);
+    assert!(!matches!(Parser::new("a | b\n---\n").next(), Some(Item::Table(_))));
+}
+
+#[test]
+fn test_easy_mark_task() {
+    let items: Vec<_> = Parser::new("# todo\n- [ ] write\n  - [x] done\n- [link](url)").collect();
+    assert_eq!(
+        items,
+        vec![
+            Item::Text(Style { heading: Some(1), ..Default::default() }, "todo".into()),
+            Item::Newline,
+            Item::Task(false, Some(2)),
+            Item::Text(Style::default(), "write".into()),
+            Item::Newline,
+            Item::Indentation(2),
+            Item::Task(true, Some(3)),
+            Item::Text(Style::default(), "done".into()),
+            Item::Newline,
+            Item::BulletPoint,
+            Item::Hyperlink(Style::default(), "link".into(), "url".into()),
+        ]
+    );
+    assert_eq!(Parser::new("> - [ ] quoted").nth(1), Some(Item::Task(false, None)));
+    let table = Table::new("| task |\n|---|\n| - [x] cell |", Syntax::EasyMark);
+    assert_eq!(table.parse_cell(table.rows()[0][0])[0], Item::Task(true, None));
+}
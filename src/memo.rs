@@ -1,6 +1,10 @@
+use crate::markup::Syntax;
+use crate::link;
+use chrono::{DateTime, Local, NaiveDate};
+use std::collections::BTreeMap;
 use std::fmt;
 use std::fs;
-use std::path::{PathBuf};
+use std::path::{Path, PathBuf};
 use std::error::Error;
 use serde::{Serialize, Deserialize};
 
@@ -8,6 +12,7 @@ use serde::{Serialize, Deserialize};
 pub struct Memo {
     path: String,
     tags: Vec<String>,
+    links: Vec<String>,
 }
 
 impl Memo {
@@ -15,8 +20,21 @@ impl Memo {
         Memo {
             path: path,
             tags: tags,
+            links: Vec::new(),
         }
     }
+
+    /// 本文の`[[link]]`のリンク先を設定します。
+    pub fn with_links(mut self, links: Vec<String>) -> Self {
+        self.links = links;
+        self
+    }
+
+    /// 本文の`[[link]]`のリンク先(メモのタイトルかファイル名)
+    pub fn get_links(&self) -> &Vec<String> {
+        &self.links
+    }
+
     pub fn get_tags(&self) -> &Vec<String> {
         &self.tags
     }
@@ -24,6 +42,41 @@ impl Memo {
     pub fn get_path(&self) -> &String {
         &self.path
     }
+
+    /// ファイル名から`yymmdd_`の接頭辞と拡張子を除いたタイトル
+    pub fn get_title(&self) -> String {
+        let stem = Path::new(&self.path).file_stem().map_or("".to_string(), |x| x.to_string_lossy().to_string());
+        match split_date_prefix(&stem) {
+            Some((_, title)) => title.to_string(),
+            None => stem,
+        }
+    }
+
+    /// ファイル名の`yymmdd_`から作成日を返します。
+    pub fn get_date_created(&self) -> Option<NaiveDate> {
+        let stem = Path::new(&self.path).file_stem()?.to_string_lossy().to_string();
+        split_date_prefix(&stem).map(|(date, _)| date)
+    }
+
+    /// ファイルの更新日を返します。
+    pub fn get_date_modified(&self) -> Option<NaiveDate> {
+        let modified = fs::metadata(&self.path).ok()?.modified().ok()?;
+        Some(DateTime::<Local>::from(modified).date_naive())
+    }
+
+    /// 更新日、なければ作成日を返します。
+    pub fn get_date_updated(&self) -> Option<NaiveDate> {
+        self.get_date_modified().or_else(|| self.get_date_created())
+    }
+}
+
+/// `yymmdd_title`を日付とタイトルに分けます。
+pub fn split_date_prefix(stem: &str) -> Option<(NaiveDate, &str)> {
+    if stem.len() < 7 || !stem.is_char_boundary(7) || &stem[6..7] != "_" {
+        return None;
+    }
+    let date = NaiveDate::parse_from_str(&stem[..6], "%y%m%d").ok()?;
+    Some((date, &stem[7..]))
 }
 
 impl fmt::Display for Memo {
@@ -41,7 +94,31 @@ impl fmt::Display for Memo {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Setting {
     path_memo: Vec<String>,
-    app_using_openmemo: String
+    app_using_openmemo: String,
+    /// `add --template`で使うテンプレート(`<名前>.md`)を置くディレクトリ
+    #[serde(default)]
+    path_template: Option<String>,
+    /// 日誌を置くディレクトリ。無い場合は`path_memo`の最初のディレクトリ
+    #[serde(default)]
+    path_journal: Option<String>,
+    /// 日誌を作るときに使うテンプレートの名前
+    #[serde(default)]
+    template_journal: Option<String>,
+    /// メモを作ったり書き換えたりしたときに、メモのディレクトリのgitリポジトリへコミットするか。リポジトリは`git init`で作っておく
+    #[serde(default)]
+    use_git: Option<bool>,
+    /// `archive`でメモを移動するディレクトリ。無い場合はメモのディレクトリの`archive`
+    #[serde(default)]
+    path_archive: Option<String>,
+    /// メモごとに残すスナップショットの数。0なら制限しない
+    #[serde(default)]
+    history_max_count: Option<usize>,
+    /// スナップショットを残す日数
+    #[serde(default)]
+    history_max_days: Option<i64>,
+    /// メモをCommonMarkとして表示するディレクトリ。それ以外のディレクトリのメモはEasyMarkとして表示する
+    #[serde(default)]
+    path_common_mark: Option<Vec<String>>,
 }
 
 impl Setting {
@@ -52,9 +129,119 @@ impl Setting {
     pub fn get_app_using_openmemo(&self) -> &String {
         &self.app_using_openmemo
     }
+
+    pub fn get_template_path(&self) -> Option<&String> {
+        self.path_template.as_ref()
+    }
+
+    pub fn get_journal_path(&self) -> Option<&String> {
+        self.path_journal.as_ref().or_else(|| self.path_memo.first())
+    }
+
+    pub fn get_journal_template(&self) -> Option<&String> {
+        self.template_journal.as_ref()
+    }
+
+    pub fn get_archive_path(&self) -> Option<&String> {
+        self.path_archive.as_ref()
+    }
+
+    pub fn is_git_enabled(&self) -> bool {
+        self.use_git.unwrap_or(false)
+    }
+
+    pub fn get_history_max_count(&self) -> Option<usize> {
+        match self.history_max_count {
+            Some(0) => None,
+            Some(count) => Some(count),
+            None => Some(50),
+        }
+    }
+
+    pub fn get_history_max_days(&self) -> Option<i64> {
+        self.history_max_days
+    }
+
+    pub fn get_common_mark_paths(&self) -> &[String] {
+        self.path_common_mark.as_deref().unwrap_or_default()
+    }
+
+    /// `path`のメモの書き方
+    pub fn get_syntax(&self, path: &str) -> Syntax {
+        get_syntax(path, self.get_common_mark_paths())
+    }
+}
+
+/// `dirs`の中のメモはCommonMark、それ以外のメモはEasyMarkとして読みます。
+pub fn get_syntax(path: &str, dirs: &[String]) -> Syntax {
+    if dirs.iter().any(|dir| Path::new(path).starts_with(dir)) {
+        Syntax::CommonMark
+    } else {
+        Syntax::EasyMark
+    }
+}
+
+/// ファイルを読み込みます。UTF-8で読めない場合はShift_JISとして読みます。
+pub fn read_text(path: &Path) -> std::io::Result<String> {
+    read_text_with_encoding(path).map(|(text, _)| text)
+}
+
+/// ファイルを読み込み、読んだときの文字コードと一緒に返します。
+pub fn read_text_with_encoding(path: &Path) -> std::io::Result<(String, &'static encoding_rs::Encoding)> {
+    Ok(decode_text(fs::read(path)?))
+}
+
+/// UTF-8で読めない場合はShift_JISとして読み、読んだときの文字コードと一緒に返します。
+pub fn decode_text(bytes: Vec<u8>) -> (String, &'static encoding_rs::Encoding) {
+    match String::from_utf8(bytes) {
+        Ok(text) => (text, encoding_rs::UTF_8),
+        Err(e) => {
+            let (res, _, _) = encoding_rs::SHIFT_JIS.decode(e.as_bytes());
+            (res.into_owned(), encoding_rs::SHIFT_JIS)
+        }
+    }
+}
+
+/// `read_text_with_encoding`で読んだときの文字コードでファイルに書き込みます。
+pub fn write_text(path: &Path, text: &str, encoding: &'static encoding_rs::Encoding) -> std::io::Result<()> {
+    let (bytes, _, _) = encoding.encode(text);
+    fs::write(path, bytes)
+}
+
+/// `add`でメモの先頭に書くタグのヘッダー
+pub fn create_tags_header(tags: &[String]) -> String {
+    let tags_out: String = tags.iter().map(|tag| format!("#{} ", tag)).collect();
+    format!(" <!---\n tags: {}\n --->\n", tags_out)
+}
+
+/// 本文の先頭の`<!--- tags: ... --->`を除きます。
+pub fn strip_tags_header(text: &str) -> &str {
+    let trimmed = text.trim_start();
+    if trimmed.starts_with("<!---") {
+        if let Some(end) = trimmed.find("--->") {
+            let rest = &trimmed[end + "--->".len()..];
+            return rest.strip_prefix("\r\n").or_else(|| rest.strip_prefix('\n')).unwrap_or(rest);
+        }
+    }
+    text
 }
 
+/// 添付ファイルを置くディレクトリの名前
+pub const DIR_ASSETS: &str = "assets";
 
+/// メモの添付ファイルを置くディレクトリ(メモと同じ場所の`assets/<メモのファイル名>`)
+pub fn get_assets_dir(path: &Path) -> PathBuf {
+    let stem = path.file_stem().map(|x| x.to_string_lossy().into_owned()).unwrap_or_default();
+    path.parent().unwrap_or_else(|| Path::new("")).join(DIR_ASSETS).join(stem)
+}
+
+/// コマンドで指定されたメモを、パス、ファイル名、タイトルの順に探します。
+pub fn find_memo<'a>(name: &str, lst_memo: &'a [Memo]) -> Option<&'a Memo> {
+    lst_memo
+        .iter()
+        .find(|memo| Path::new(memo.get_path()) == Path::new(name))
+        .or_else(|| link::resolve_wiki_link(name, lst_memo))
+}
 
 pub fn read_dir(path: &str) -> Result<Vec<PathBuf>, Box<dyn Error>> {
     let dir = fs::read_dir(path)?;
@@ -100,20 +287,18 @@ pub fn create_memo_from_file(file: &PathBuf) -> Option<Memo> {
         }
     };
 
-    let lines = text.lines();
-
-    lines.into_iter().find_map(|line| {
-        match get_tags_by_line(line.to_string()) {
-            Some(tags) => {
-                Some(Memo::new(
-                    file.to_str().unwrap().replace("\\", "/").to_string(),
-                    tags,
-                ))
-            },
-            None => None,
-        }
-    })
+    let tags = get_tags_from_text(&text)?;
+    let memo = Memo::new(file.to_str().unwrap().replace("\\", "/").to_string(), tags);
+
+    // バックリンクを引けるよう、リンク先を一緒に覚えておく
+    let links = link::extract_wiki_links(&text).into_iter().map(|link| link.target).collect();
+    Some(memo.with_links(links))
+
+}
 
+/// 本文で最初に`tags`を含む行からタグを読み取ります。
+pub fn get_tags_from_text(text: &str) -> Option<Vec<String>> {
+    text.lines().find_map(|line| get_tags_by_line(line.to_string()))
 }
 
 /// 渡されたpathに存在するmdファイルをメモとして返します。
@@ -123,7 +308,7 @@ fn get_tags_by_line(mut line: String) -> Option<Vec<String>> {
             line.retain(|x| x != ' ');
 
             let mut tags: Vec<&str> = line.split('#').collect();
-            tags.retain(|x| !x.contains("tags:"));
+            tags.retain(|x| !x.contains("tags:") && !x.is_empty());
             Some(tags.iter().map(|x| x.to_string()).collect())
         }
         false => {
@@ -134,11 +319,111 @@ fn get_tags_by_line(mut line: String) -> Option<Vec<String>> {
 
 pub fn is_include_these_tags(tags: &Vec<String>, tags_memo: &Vec<String>) -> bool {
     let mut tags_dummy = tags.clone();
-    tags_dummy.retain(|tag| tags_memo.iter().all(|tag_memo| !tag.contains(tag_memo)));
+    tags_dummy.retain(|tag| tags_memo.iter().all(|tag_memo| !is_match_tag(tag, tag_memo)));
 
     tags_dummy.is_empty()
 }
 
+/// 検索タグがメモのタグに一致するかを返します。
+/// `work/projectX/design`のような階層タグは、`work`や`work/projectX`での検索にも一致します。
+pub fn is_match_tag(tag: &str, tag_memo: &str) -> bool {
+    if tag.contains('/') || tag_memo.contains('/') {
+        tag_memo == tag || tag_memo.starts_with(&format!("{}/", tag))
+    } else {
+        tag.contains(tag_memo)
+    }
+}
+
+/// タグ(`all`を含む場合は全件)でメモを絞り込みます。
+pub fn filter_memo_by_tags(lst_memo: &[Memo], tags: &Vec<String>) -> Vec<Memo> {
+    if tags.iter().any(|x| x.contains("all")) {
+        lst_memo.to_vec()
+    } else {
+        lst_memo
+            .iter()
+            .filter(|memo| is_include_these_tags(tags, memo.get_tags()))
+            .cloned()
+            .collect()
+    }
+}
+
+/// `/`区切りの階層タグの1ノード
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagNode {
+    name: String,
+    path: String,
+    count: usize,
+    children: Vec<TagNode>,
+}
+
+impl TagNode {
+    fn new(name: &str, path: String) -> Self {
+        TagNode {
+            name: name.to_string(),
+            path,
+            count: 0,
+            children: Vec::new(),
+        }
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    /// `work/projectX`のような、ルートからのタグ
+    pub fn get_path(&self) -> &String {
+        &self.path
+    }
+
+    /// このタグ、または子孫のタグを持つメモの数
+    pub fn get_count(&self) -> usize {
+        self.count
+    }
+
+    pub fn get_children(&self) -> &Vec<TagNode> {
+        &self.children
+    }
+}
+
+/// メモのタグから階層タグのツリーを作ります。ノードは名前順に並びます。
+pub fn create_tag_tree(lst_memo: &[Memo]) -> Vec<TagNode> {
+    // 1つのメモを同じノードで複数回数えないよう、ノードごとにメモの番号を集める
+    let mut memos_of_tag: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for (i, memo) in lst_memo.iter().enumerate() {
+        for tag in memo.get_tags() {
+            let segments: Vec<&str> = tag.split('/').filter(|x| !x.is_empty()).collect();
+            for depth in 1..=segments.len() {
+                let memos = memos_of_tag.entry(segments[..depth].join("/")).or_default();
+                if memos.last() != Some(&i) {
+                    memos.push(i);
+                }
+            }
+        }
+    }
+
+    // BTreeMapの順序では親が子より先に来る
+    let mut roots: Vec<TagNode> = Vec::new();
+    for (path, memos) in memos_of_tag {
+        let segments: Vec<&str> = path.split('/').collect();
+        let mut nodes = &mut roots;
+        for (depth, segment) in segments.iter().enumerate() {
+            let pos = match nodes.iter().position(|node| &node.name == segment) {
+                Some(pos) => pos,
+                None => {
+                    nodes.push(TagNode::new(segment, segments[..=depth].join("/")));
+                    nodes.len() - 1
+                }
+            };
+            if depth + 1 == segments.len() {
+                nodes[pos].count = memos.len();
+            }
+            nodes = &mut nodes[pos].children;
+        }
+    }
+
+    roots
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -166,4 +451,38 @@ mod tests {
             true
         );
     }
+
+    #[test]
+    fn is_include_these_tags_hierarchy_test() {
+        let tags_memo = vec!["work/projectX/design".to_string()];
+        assert!(is_include_these_tags(&vec!["work".to_string()], &tags_memo));
+        assert!(is_include_these_tags(&vec!["work/projectX".to_string()], &tags_memo));
+        assert!(!is_include_these_tags(&vec!["work/project".to_string()], &tags_memo));
+        assert!(!is_include_these_tags(&vec!["design".to_string()], &tags_memo));
+    }
+
+    #[test]
+    fn get_title_test() {
+        let memo = Memo::new("E:/memo/211003_meeting.md".to_string(), vec![]);
+        assert_eq!(memo.get_title(), "meeting");
+        assert_eq!(memo.get_date_created(), NaiveDate::from_ymd_opt(2021, 10, 3));
+        let memo = Memo::new("E:/memo/todo.md".to_string(), vec![]);
+        assert_eq!(memo.get_title(), "todo");
+        assert_eq!(memo.get_date_created(), None);
+    }
+
+    #[test]
+    fn create_tag_tree_test() {
+        let lst_memo = vec![
+            Memo::new("a.md".to_string(), vec!["work/projectX/design".to_string(), "work/projectX".to_string()]),
+            Memo::new("b.md".to_string(), vec!["work/projectY".to_string(), "idea".to_string()]),
+        ];
+        let tree = create_tag_tree(&lst_memo);
+        assert_eq!(tree.iter().map(|x| x.get_name().as_str()).collect::<Vec<_>>(), vec!["idea", "work"]);
+        let work = &tree[1];
+        assert_eq!(work.get_count(), 2);
+        assert_eq!(work.get_children()[0].get_path(), "work/projectX");
+        assert_eq!(work.get_children()[0].get_count(), 1);
+        assert_eq!(work.get_children()[0].get_children()[0].get_count(), 1);
+    }
 }
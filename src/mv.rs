@@ -0,0 +1,250 @@
+use crate::archive;
+use crate::check;
+use crate::history;
+use crate::link;
+use crate::memo;
+use crate::path;
+use anyhow::{bail, Context, Result};
+use regex::{Captures, Regex};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// 本文の`[[link]]`と`[text](url)`のリンク先を書き換えます。コードブロックの中は書き換えません。
+/// `rename_wiki`と`rename_url`は新しいリンク先を返し、書き換えない場合は`None`を返します。
+/// 1つも書き換えなかった場合は`None`を返します。
+pub fn rewrite_links<W, U>(text: &str, rename_wiki: W, rename_url: U) -> Option<String>
+where
+    W: Fn(&str) -> Option<String>,
+    U: Fn(&str) -> Option<String>,
+{
+    let re_link = Regex::new(r"(!?\[[^\]]*\]\()([^)\s]+)\)").unwrap();
+    let mut is_changed = false;
+    let mut is_code_block = false;
+    let mut text_new = String::new();
+    for line in text.split_inclusive('\n') {
+        if line.trim_start().starts_with("```") {
+            is_code_block = !is_code_block;
+        }
+        if is_code_block || line.trim_start().starts_with("```") {
+            text_new += line;
+            continue;
+        }
+        let line = re_link.replace_all(line, |cap: &Captures| match rename_url(&cap[2]) {
+            Some(url) if url != cap[2] => {
+                is_changed = true;
+                format!("{}{})", &cap[1], url)
+            }
+            _ => cap[0].to_string(),
+        });
+        let mut rest: &str = &line;
+        while let Some(start) = rest.find("[[") {
+            text_new += &rest[..start];
+            match link::parse_wiki_link(&rest[start..]) {
+                Some((target, label, len)) => {
+                    let has_label = rest[start..start + len].contains('|');
+                    let target_new = rename_wiki(target).filter(|x| x != target);
+                    is_changed |= target_new.is_some();
+                    match target_new {
+                        Some(target_new) if has_label => text_new += &format!("[[{}|{}]]", target_new, label),
+                        Some(target_new) => text_new += &format!("[[{}]]", target_new),
+                        None => text_new += &rest[start..start + len],
+                    }
+                    rest = &rest[start + len..];
+                }
+                None => {
+                    text_new += "[[";
+                    rest = &rest[start + 2..];
+                }
+            }
+        }
+        text_new += rest;
+    }
+    Some(text_new).filter(|_| is_changed)
+}
+
+/// `from_dir`から`to`への相対パス。`/`で区切ります。ドライブが違う場合は`to`をそのまま返します。
+pub fn get_relative_path(from_dir: &Path, to: &Path) -> String {
+    let from: Vec<Component> = from_dir.components().collect();
+    let to_components: Vec<Component> = to.components().collect();
+    if from.first() != to_components.first() {
+        return to.to_string_lossy().replace('\\', "/");
+    }
+    let n_common = from.iter().zip(to_components.iter()).take_while(|(a, b)| a == b).count();
+    let mut parts: Vec<String> = vec!["..".to_string(); from.len() - n_common];
+    parts.extend(to_components[n_common..].iter().map(|x| x.as_os_str().to_string_lossy().into_owned()));
+    parts.join("/")
+}
+
+/// `.`と`..`を取り除いたパス。存在しないファイルにも使えるよう、ファイルシステムは見ません。
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// 移動するメモ自身のリンクを、`path_old`から`path_new`へ移動した後も同じファイルを指すように書き換えます。
+/// 添付ファイルのディレクトリ(`assets/<メモのファイル名>`)の中はメモと一緒に移動します。
+/// 書き換えない場合は`None`を返します。
+pub fn rebase_own_link(url: &str, path_old: &Path, path_new: &Path) -> Option<String> {
+    if check::is_external_url(url) {
+        return None;
+    }
+    let local = check::to_local_path(url);
+    if local.is_empty() {
+        return None;
+    }
+    let dir_old = path_old.parent().unwrap_or_else(|| Path::new(""));
+    let dir_new = path_new.parent().unwrap_or_else(|| Path::new(""));
+    let target = normalize_path(&dir_old.join(&local));
+    let target_new = if target == path_old {
+        path_new.to_path_buf()
+    } else if let Ok(rest) = target.strip_prefix(memo::get_assets_dir(path_old)) {
+        memo::get_assets_dir(path_new).join(rest)
+    } else if dir_old == dir_new || Path::new(&local).is_absolute() {
+        return None;
+    } else {
+        target
+    };
+    let fragment = url.find('#').map(|x| &url[x..]).unwrap_or("");
+    Some(format!("{}{}", get_relative_path(dir_new, &target_new).replace(' ', "%20"), fragment))
+}
+
+/// `yymmdd_`を残して新しいタイトルにしたパス
+pub fn get_renamed_path(path: &Path, title: &str) -> Result<PathBuf> {
+    let title = path::sanitize_file_name(title);
+    if title.is_empty() {
+        bail!("new title is empty");
+    }
+    let stem = path.file_stem().map(|x| x.to_string_lossy().into_owned()).unwrap_or_default();
+    let prefix = match memo::split_date_prefix(&stem) {
+        Some((_, title_old)) => &stem[..stem.len() - title_old.len()],
+        None => "",
+    };
+    let ext = path.extension().map(|x| format!(".{}", x.to_string_lossy())).unwrap_or_default();
+    Ok(path.with_file_name(format!("{}{}{}", prefix, title, ext)))
+}
+
+/// メモを`path_new`へ移動し、他のメモのリンクと、移動したメモ自身の相対リンクを書き換えます。
+/// 書き換えたメモのパスを返します。書き換えるメモはスナップショットを残してから書き換えます。
+pub fn move_memo_and_links(
+    memo_target: &memo::Memo,
+    path_new: &Path,
+    lst_memo: &[memo::Memo],
+    retention: history::Retention,
+) -> Result<Vec<PathBuf>> {
+    let path_old = Path::new(memo_target.get_path());
+    let path_old_abs = fs::canonicalize(path_old).with_context(|| format!("can't find {}", path_old.display()))?;
+    // 大文字と小文字を区別しないファイルシステムでは、大文字小文字だけの変更は同じファイルになる
+    if path_new.exists() && fs::canonicalize(path_new).ok().as_ref() != Some(&path_old_abs) {
+        bail!("{} already exists", path_new.display());
+    }
+    let dir_new = path_new.parent().unwrap_or_else(|| Path::new(""));
+    let dir_new_abs = fs::canonicalize(dir_new).with_context(|| format!("can't find {}", dir_new.display()))?;
+    let path_new_abs = dir_new_abs.join(path_new.file_name().unwrap_or_default());
+
+    let stem_old = path_old.file_stem().map(|x| x.to_string_lossy().to_lowercase()).unwrap_or_default();
+    let stem_new = path_new.file_stem().map(|x| x.to_string_lossy().into_owned()).unwrap_or_default();
+    let title_new = memo::Memo::new(path_new.to_string_lossy().into_owned(), Vec::new()).get_title();
+
+    // 移動する前に、リンクが移動するメモを指しているかを確かめておく
+    let mut lst_rewritten: Vec<(PathBuf, String, &'static encoding_rs::Encoding)> = Vec::new();
+    for memo in lst_memo.iter().filter(|memo| memo.get_path() != memo_target.get_path()) {
+        let path = Path::new(memo.get_path());
+        let (text, encoding) = match memo::read_text_with_encoding(path) {
+            Ok(text) => text,
+            Err(_) => continue,
+        };
+        let dir = fs::canonicalize(path.parent().unwrap_or_else(|| Path::new(""))).unwrap_or_default();
+        let rename_wiki = |target: &str| {
+            let memo_linked = link::resolve_wiki_link(target, lst_memo)?;
+            if memo_linked.get_path() != memo_target.get_path() {
+                return None;
+            }
+            // ファイル名でリンクしていた場合はファイル名のままにする
+            let target_lower = target.trim().to_lowercase();
+            if target_lower.strip_suffix(".md").unwrap_or(&target_lower) == stem_old {
+                Some(stem_new.clone())
+            } else {
+                Some(title_new.clone())
+            }
+        };
+        let rename_url = |url: &str| {
+            if check::is_external_url(url) {
+                return None;
+            }
+            let target = fs::canonicalize(dir.join(check::to_local_path(url))).ok()?;
+            if target != path_old_abs {
+                return None;
+            }
+            let fragment = url.find('#').map(|x| &url[x..]).unwrap_or("");
+            Some(format!("{}{}", get_relative_path(&dir, &path_new_abs).replace(' ', "%20"), fragment))
+        };
+        if let Some(text_new) = rewrite_links(&text, rename_wiki, rename_url) {
+            lst_rewritten.push((path.to_path_buf(), text_new, encoding));
+        }
+    }
+
+    // 移動するメモ自身のリンク
+    let (text_target, encoding_target) =
+        memo::read_text_with_encoding(path_old).with_context(|| format!("can't read {}", path_old.display()))?;
+    if let Some(text_new) = rewrite_links(&text_target, |_| None, |url| rebase_own_link(url, &path_old_abs, &path_new_abs)) {
+        lst_rewritten.push((path_new.to_path_buf(), text_new, encoding_target));
+    }
+
+    archive::move_memo(path_old, path_new)?;
+    let now = chrono::Local::now().naive_local();
+    for (path, text, encoding) in &lst_rewritten {
+        history::save_snapshot(path, now, retention)?;
+        memo::write_text(path, text, encoding).with_context(|| format!("can't write {}", path.display()))?;
+    }
+    Ok(lst_rewritten.into_iter().map(|(path, _, _)| path).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn rewrite_links_test() {
+        let text = "see [[Design]] and [[211003_design|the design]]\r\n[doc](211003_design.md#top) [[other]]\n```\n[[Design]]\n```\n";
+        let rename_wiki = |target: &str| match target {
+            "Design" => Some("spec".to_string()),
+            "211003_design" => Some("211003_spec".to_string()),
+            _ => None,
+        };
+        let rename_url = |url: &str| Some(url.replace("design", "spec")).filter(|x| x != url);
+        assert_eq!(
+            rewrite_links(text, rename_wiki, rename_url).unwrap(),
+            "see [[spec]] and [[211003_spec|the design]]\r\n[doc](211003_spec.md#top) [[other]]\n```\n[[Design]]\n```\n"
+        );
+        assert_eq!(rewrite_links("[[other]]", rename_wiki, rename_url), None);
+    }
+
+    #[test]
+    fn get_renamed_path_test() {
+        assert_eq!(
+            get_renamed_path(Path::new("no_such_dir/211003_design.md"), "spec").unwrap(),
+            PathBuf::from("no_such_dir/211003_spec.md")
+        );
+        assert_eq!(get_relative_path(Path::new("/memo/work"), Path::new("/memo/life/a b.md")), "../life/a b.md");
+    }
+
+    #[test]
+    fn rebase_own_link_test() {
+        let path_old = Path::new("/memo/211003_design.md");
+        let path_new = Path::new("/memo/work/211003_spec.md");
+        let rebase = |url: &str| rebase_own_link(url, path_old, path_new);
+        assert_eq!(rebase("assets/211003_design/floor%20plan.png").unwrap(), "assets/211003_spec/floor%20plan.png");
+        assert_eq!(rebase("./assets/spec.pdf#page=2").unwrap(), "../assets/spec.pdf#page=2");
+        assert_eq!(rebase("211003_design.md#top").unwrap(), "211003_spec.md#top");
+        assert_eq!(rebase("https://example.com/a.png"), None);
+        assert_eq!(rebase_own_link("other.md", path_old, Path::new("/memo/211003_spec.md")), None);
+    }
+}
@@ -0,0 +1,46 @@
+use std::path::PathBuf;
+
+/// リンクに書けない文字をエスケープします。
+pub fn encode_link(name: &str) -> String {
+    name.replace('%', "%25").replace(' ', "%20").replace('(', "%28").replace(')', "%29")
+}
+
+/// 画像として埋め込むファイルか
+pub fn is_image(name: &str) -> bool {
+    [".png", ".jpg", ".jpeg", ".gif", ".bmp", ".svg", ".webp"]
+        .iter()
+        .any(|ext| name.to_lowercase().ends_with(ext))
+}
+
+/// ファイル名に使えない文字を`_`にします。
+pub fn sanitize_file_name(name: &str) -> String {
+    name.trim()
+        .chars()
+        .map(|c| if "\\/:*?\"<>|".contains(c) || c.is_control() { '_' } else { c })
+        .collect()
+}
+
+/// `path`が既にあれば`_2`のように番号を付けたパスを返します。
+pub fn get_unique_path(path: PathBuf) -> PathBuf {
+    let stem = path.file_stem().map(|x| x.to_string_lossy().into_owned()).unwrap_or_default();
+    let ext = path.extension().map(|x| format!(".{}", x.to_string_lossy())).unwrap_or_default();
+    let mut path_unique = path.clone();
+    let mut n = 2;
+    while path_unique.exists() {
+        path_unique = path.with_file_name(format!("{}_{}{}", stem, n, ext));
+        n += 1;
+    }
+    path_unique
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn file_name_test() {
+        assert_eq!(encode_link("a b(1)%.png"), "a%20b%281%29%25.png");
+        assert!(is_image("Map.PNG"));
+        assert!(!is_image("map.pdf"));
+        assert_eq!(sanitize_file_name(" a/b:c?.md "), "a_b_c_.md");
+    }
+}
@@ -0,0 +1,109 @@
+use crate::memo;
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+/// 一緒に付けられているタグとその回数
+#[derive(Debug, Serialize, PartialEq)]
+pub struct CoOccurrence {
+    pub tag: String,
+    pub count: usize,
+}
+
+/// タグごとのメモ数、最終使用日、よく一緒に付けられるタグ
+#[derive(Debug, Serialize)]
+pub struct TagStat {
+    pub tag: String,
+    pub count: usize,
+    pub last_used: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub co_occurrence: Vec<CoOccurrence>,
+}
+
+/// メモ一覧からタグの集計を作ります。メモ数の多い順に並びます。
+/// `n_co_occurrence`が0でなければ、タグごとによく一緒に付けられるタグを上位その数だけ集計します。
+pub fn create_tag_stats(lst_memo: &[memo::Memo], n_co_occurrence: usize) -> Vec<TagStat> {
+    let mut stats: BTreeMap<&str, TagStat> = BTreeMap::new();
+    let mut co_occurrence: BTreeMap<&str, BTreeMap<&str, usize>> = BTreeMap::new();
+
+    for memo in lst_memo {
+        let mut tags: Vec<&str> = memo.get_tags().iter().map(|x| x.as_str()).collect();
+        tags.sort_unstable();
+        tags.dedup();
+        let date = memo.get_date_updated().map(|x| x.format("%Y-%m-%d").to_string());
+
+        for tag in &tags {
+            let stat = stats.entry(tag).or_insert_with(|| TagStat {
+                tag: tag.to_string(),
+                count: 0,
+                last_used: None,
+                co_occurrence: Vec::new(),
+            });
+            stat.count += 1;
+            // yyyy-mm-dd形式なので文字列の比較で新しい方が分かる
+            if date.is_some() && stat.last_used < date {
+                stat.last_used = date.clone();
+            }
+            if n_co_occurrence > 0 {
+                let counts = co_occurrence.entry(tag).or_default();
+                for other in tags.iter().filter(|x| *x != tag) {
+                    *counts.entry(other).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+
+    let mut stats: Vec<TagStat> = stats.into_values().collect();
+    for stat in stats.iter_mut() {
+        if let Some(counts) = co_occurrence.get(stat.tag.as_str()) {
+            let mut counts: Vec<(&&str, &usize)> = counts.iter().collect();
+            counts.sort_by_key(|x| Reverse(*x.1));
+            stat.co_occurrence = counts
+                .into_iter()
+                .take(n_co_occurrence)
+                .map(|(tag, count)| CoOccurrence { tag: tag.to_string(), count: *count })
+                .collect();
+        }
+    }
+    stats.sort_by_key(|x| Reverse(x.count));
+    stats
+}
+
+pub fn print_tag_stats_table(stats: &[TagStat]) {
+    let width = stats.iter().map(|x| x.tag.chars().count()).max().unwrap_or(0).max(3);
+    println!("{:<width$} | {:>5} | {:<10} | co-occurrence", "tag", "count", "last used", width = width);
+    for stat in stats {
+        let co_occurrence: Vec<String> = stat
+            .co_occurrence
+            .iter()
+            .map(|x| format!("{}({})", x.tag, x.count))
+            .collect();
+        println!(
+            "{:<width$} | {:>5} | {:<10} | {}",
+            stat.tag,
+            stat.count,
+            stat.last_used.as_deref().unwrap_or("-"),
+            co_occurrence.join(", "),
+            width = width
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn create_tag_stats_test() {
+        let lst_memo = vec![
+            memo::Memo::new("211001_a.md".to_string(), vec!["work".to_string(), "idea".to_string()]),
+            memo::Memo::new("211003_b.md".to_string(), vec!["work".to_string(), "idea".to_string()]),
+            memo::Memo::new("211002_c.md".to_string(), vec!["work".to_string(), "bug".to_string()]),
+        ];
+        let stats = create_tag_stats(&lst_memo, 1);
+        assert_eq!(stats[0].tag, "work");
+        assert_eq!(stats[0].count, 3);
+        assert_eq!(stats[0].last_used.as_deref(), Some("2021-10-03"));
+        assert_eq!(stats[0].co_occurrence, vec![CoOccurrence { tag: "idea".to_string(), count: 2 }]);
+        assert_eq!(stats[1].tag, "idea");
+    }
+}
@@ -0,0 +1,317 @@
+use crate::memo;
+use anyhow::{bail, Result};
+use chrono::{Duration, NaiveDate};
+use std::collections::HashSet;
+use std::path::Path;
+
+/// 表示するタスクのIDの最短の長さ
+const ID_LEN_MIN: usize = 6;
+
+/// `!high`、`!medium`、`!low`で書く優先度。並べると高い順になります。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    High,
+    Medium,
+    Low,
+}
+
+/// メモの中の`- [ ]`、`- [x]`のチェックボックス1つ
+#[derive(Debug, Clone, PartialEq)]
+pub struct Task {
+    id: String,
+    path: String,
+    line: usize,
+    text: String,
+    is_done: bool,
+    tags: Vec<String>,
+    due: Option<NaiveDate>,
+    priority: Option<Priority>,
+}
+
+impl Task {
+    pub fn get_id(&self) -> &String {
+        &self.id
+    }
+
+    pub fn get_path(&self) -> &String {
+        &self.path
+    }
+
+    pub fn get_text(&self) -> &String {
+        &self.text
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.is_done
+    }
+
+    /// タスクが書かれているメモのタグ
+    pub fn get_tags(&self) -> &Vec<String> {
+        &self.tags
+    }
+
+    /// `@due(2021-10-20)`で書かれた期日
+    pub fn get_due(&self) -> Option<NaiveDate> {
+        self.due
+    }
+
+    pub fn get_priority(&self) -> Option<Priority> {
+        self.priority
+    }
+
+    /// 期日を過ぎた未完了のタスクか
+    pub fn is_overdue(&self, today: NaiveDate) -> bool {
+        !self.is_done && self.due.is_some_and(|due| due < today)
+    }
+
+    /// 今日から`days`日以内が期日の未完了のタスクか
+    pub fn is_due_within(&self, today: NaiveDate, days: i64) -> bool {
+        !self.is_done && self.due.is_some_and(|due| today <= due && due <= today + Duration::days(days))
+    }
+}
+
+/// 本文から`@due(yyyy-mm-dd)`を探します。
+pub fn parse_due(text: &str) -> Option<NaiveDate> {
+    let start = text.find("@due(")? + "@due(".len();
+    let end = start + text[start..].find(')')?;
+    NaiveDate::parse_from_str(text[start..end].trim(), "%Y-%m-%d").ok()
+}
+
+/// 本文から`!high`、`!medium`(`!mid`)、`!low`を探します。
+pub fn parse_priority(text: &str) -> Option<Priority> {
+    text.split_whitespace().find_map(|word| match word {
+        "!high" => Some(Priority::High),
+        "!medium" | "!mid" => Some(Priority::Medium),
+        "!low" => Some(Priority::Low),
+        _ => None,
+    })
+}
+
+/// `3d`、`2w`、`3`(日)のような期間を日数にします。
+pub fn parse_days(days: &str) -> Result<i64> {
+    let (number, unit) = match days.strip_suffix('d') {
+        Some(number) => (number, 1),
+        None => match days.strip_suffix('w') {
+            Some(number) => (number, 7),
+            None => (days, 1),
+        },
+    };
+    // 負の期間は受け付けない
+    match number.parse::<u32>() {
+        Ok(number) => Ok(i64::from(number) * unit),
+        Err(_) => bail!("period must be like 3d or 2w: {}", days),
+    }
+}
+
+/// 期日の近い順、同じ期日なら優先度の高い順に並べます。期日の無いタスクは最後になります。
+pub fn sort_by_due(tasks: &mut [Task]) {
+    tasks.sort_by_key(|task| (task.due.is_none(), task.due, task.priority.is_none(), task.priority));
+}
+
+/// リストの記号(`- `、`* `、`+ `、`1. `、`1) `)より後ろを返します。
+fn strip_list_marker(line: &str) -> Option<&str> {
+    let trimmed = line.trim_start();
+    if let Some(rest) = trimmed
+        .strip_prefix("- ")
+        .or_else(|| trimmed.strip_prefix("* "))
+        .or_else(|| trimmed.strip_prefix("+ "))
+    {
+        return Some(rest);
+    }
+    // 番号付きリストの番号は9桁まで
+    let digits = trimmed.len() - trimmed.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    if digits == 0 || digits > 9 {
+        return None;
+    }
+    trimmed[digits..].strip_prefix(". ").or_else(|| trimmed[digits..].strip_prefix(") "))
+}
+
+/// `- [ ] text`の行を、チェックの有無と本文に分けます。
+pub fn parse_task_line(line: &str) -> Option<(bool, &str)> {
+    let rest = strip_list_marker(line)?;
+    if let Some(text) = rest.strip_prefix("[ ]") {
+        Some((false, text.trim()))
+    } else if let Some(text) = rest.strip_prefix("[x]").or_else(|| rest.strip_prefix("[X]")) {
+        Some((true, text.trim()))
+    } else {
+        None
+    }
+}
+
+/// チェックボックスを反転した行を返します。改行はそのまま残します。
+pub fn toggle_task_line(line: &str) -> Option<String> {
+    let (is_done, _) = parse_task_line(line)?;
+    // リストの記号の後ろにチェックボックスがある
+    let start = line.len() - strip_list_marker(line)?.len();
+    let checkbox = if is_done { "[ ]" } else { "[x]" };
+    Some(format!("{}{}{}", &line[..start], checkbox, &line[start + 3..]))
+}
+
+/// タスクのID。行番号が変わっても同じになるよう、パスと本文(と同じ本文の何番目か)から作ります。
+fn create_task_id(path: &str, text: &str, nth: usize) -> String {
+    // FNV-1a
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in format!("{}\n{}\n{}", path, text, nth).bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    format!("{:016x}", hash)
+}
+
+/// IDを、全てのタスクで重ならない長さ(`ID_LEN_MIN`文字以上)まで短くします。
+fn shorten_task_ids(tasks: &mut [Task]) {
+    let len_max = tasks.iter().map(|task| task.id.len()).max().unwrap_or(0);
+    let len = (ID_LEN_MIN..len_max)
+        .find(|len| {
+            let mut ids: HashSet<&str> = HashSet::new();
+            tasks.iter().all(|task| ids.insert(&task.id[..*len]))
+        })
+        .unwrap_or(len_max);
+    for task in tasks {
+        task.id.truncate(len);
+    }
+}
+
+/// `tasks`で表示したIDのタスクを探します。IDの先頭の部分でも、1つに決まれば探せます。
+pub fn find_task<'a>(tasks: &'a [Task], id: &str) -> Result<&'a Task> {
+    let found: Vec<&Task> = tasks.iter().filter(|task| !id.is_empty() && task.id.starts_with(id)).collect();
+    match found.as_slice() {
+        [task] => Ok(task),
+        [] => bail!("task {} isn't found", id),
+        _ => bail!("{} tasks start with {}. Please specify a longer id", found.len(), id),
+    }
+}
+
+/// メモの本文からタスクを取り出します。コードブロックの中は無視します。
+pub fn extract_tasks_from_text(path: &str, text: &str, tags: &[String]) -> Vec<Task> {
+    let mut tasks: Vec<Task> = Vec::new();
+    let mut is_code_block = false;
+    for (i, line) in text.lines().enumerate() {
+        if line.trim_start().starts_with("```") {
+            is_code_block = !is_code_block;
+            continue;
+        }
+        if is_code_block {
+            continue;
+        }
+        if let Some((is_done, text)) = parse_task_line(line) {
+            let nth = tasks.iter().filter(|task| task.text == text).count();
+            tasks.push(Task {
+                id: create_task_id(path, text, nth),
+                path: path.to_string(),
+                line: i + 1,
+                text: text.to_string(),
+                is_done,
+                tags: tags.to_vec(),
+                due: parse_due(text),
+                priority: parse_priority(text),
+            });
+        }
+    }
+    tasks
+}
+
+/// 全てのメモからタスクを取り出します。
+pub fn create_task_list(lst_memo: &[memo::Memo]) -> Vec<Task> {
+    let mut tasks: Vec<Task> = lst_memo
+        .iter()
+        .filter_map(|memo| {
+            let text = memo::read_text(Path::new(memo.get_path())).ok()?;
+            Some(extract_tasks_from_text(memo.get_path(), &text, memo.get_tags()))
+        })
+        .flatten()
+        .collect();
+    shorten_task_ids(&mut tasks);
+    tasks
+}
+
+/// ファイルの中のタスクのチェックボックスを反転します。文字コードと改行コードは元のままにします。
+pub fn toggle_task(task: &Task) -> Result<()> {
+    toggle_task_at(Path::new(&task.path), task.line, Some(&task.text))
+}
+
+/// ファイルの`line`行目(1から)のタスクのチェックボックスを反転します。
+/// `text`を指定した場合は、タスクの本文が変わっていないことを確かめます。
+pub fn toggle_task_at(path: &Path, line: usize, text: Option<&str>) -> Result<()> {
+    let (contents, encoding) = memo::read_text_with_encoding(path)?;
+    let mut lines: Vec<String> = contents.split_inclusive('\n').map(|x| x.to_string()).collect();
+    let toggled = match line.checked_sub(1).and_then(|i| lines.get(i)) {
+        Some(x) if text.is_none() || parse_task_line(x).map(|(_, text)| text) == text => toggle_task_line(x),
+        _ => None,
+    };
+    match toggled {
+        Some(toggled) => lines[line - 1] = toggled,
+        None => bail!("{}:{} was changed. Please list tasks again.", path.display(), line),
+    }
+    memo::write_text(path, &lines.concat(), encoding)?;
+    Ok(())
+}
+
+pub fn print_task_list(tasks: &[Task]) {
+    for task in tasks {
+        println!(
+            "{} [{}] {}  ({}:{})",
+            task.id,
+            if task.is_done { "x" } else { " " },
+            task.text,
+            task.path,
+            task.line
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn extract_tasks_from_text_test() {
+        let text = "# todo\n- [ ] write spec\n  * [x] review\n```\n- [ ] not a task\n```\n- [ ] write spec\n- [] broken\n";
+        let tasks = extract_tasks_from_text("a.md", text, &[]);
+        assert_eq!(tasks.len(), 3);
+        assert_eq!((tasks[0].line, tasks[0].is_done, tasks[0].text.as_str()), (2, false, "write spec"));
+        assert_eq!((tasks[1].line, tasks[1].is_done, tasks[1].text.as_str()), (3, true, "review"));
+        assert_eq!(tasks[2].line, 7);
+        assert_ne!(tasks[0].id, tasks[2].id);
+    }
+
+    #[test]
+    fn shorten_task_ids_test() {
+        let mut tasks = extract_tasks_from_text("a.md", "- [ ] a\n- [ ] b\n- [ ] c\n", &[]);
+        tasks[0].id = "0123456789abcdef".to_string();
+        tasks[1].id = "0123459999999999".to_string();
+        tasks[2].id = "fedcba9876543210".to_string();
+        shorten_task_ids(&mut tasks);
+        assert_eq!(tasks.iter().map(|task| task.id.as_str()).collect::<Vec<_>>(), vec!["0123456", "0123459", "fedcba9"]);
+        assert_eq!(find_task(&tasks, "fed").unwrap().text, "c");
+        assert!(find_task(&tasks, "012345").is_err());
+        assert!(find_task(&tasks, "").is_err());
+    }
+
+    #[test]
+    fn parse_due_test() {
+        let tasks = extract_tasks_from_text("a.md", "- [ ] release @due(2021-10-20) !high\n- [ ] someday !low\n", &[]);
+        assert_eq!(tasks[0].get_due(), NaiveDate::from_ymd_opt(2021, 10, 20));
+        assert_eq!(tasks[0].get_priority(), Some(Priority::High));
+        assert_eq!(tasks[1].get_due(), None);
+        assert_eq!(tasks[1].get_priority(), Some(Priority::Low));
+        let today = NaiveDate::from_ymd_opt(2021, 10, 18).unwrap();
+        assert!(tasks[0].is_due_within(today, 3));
+        assert!(!tasks[0].is_due_within(today, 1));
+        assert!(tasks[0].is_overdue(NaiveDate::from_ymd_opt(2021, 10, 21).unwrap()));
+        assert_eq!(parse_days("3d").unwrap(), 3);
+        assert_eq!(parse_days("2w").unwrap(), 14);
+        assert!(parse_days("-3d").is_err());
+        assert!(parse_days("-1").is_err());
+        assert!(parse_days("soon").is_err());
+    }
+
+    #[test]
+    fn toggle_task_line_test() {
+        assert_eq!(toggle_task_line("  - [ ] write spec\r\n").unwrap(), "  - [x] write spec\r\n");
+        assert_eq!(toggle_task_line("- [X] review").unwrap(), "- [ ] review");
+        assert_eq!(toggle_task_line("- review"), None);
+        assert_eq!(toggle_task_line("12. [ ] ship\n").unwrap(), "12. [x] ship\n");
+        assert_eq!(toggle_task_line(&toggle_task_line(" 3) [x] ship").unwrap()).unwrap(), " 3) [x] ship");
+        assert_eq!(toggle_task_line("1.[ ] ship"), None);
+    }
+}
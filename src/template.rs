@@ -0,0 +1,51 @@
+use crate::memo;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use std::env;
+use std::path::Path;
+
+/// テンプレートディレクトリから`<name>.md`を読み込みます。
+pub fn read_template(dir_template: &str, name: &str) -> Result<String> {
+    let path = Path::new(dir_template).join(format!("{}.md", name));
+    memo::read_text(&path).with_context(|| format!("can't read template {}", path.display()))
+}
+
+/// テンプレートの`{{title}}`、`{{date}}`、`{{time}}`、`{{tags}}`、`{{user}}`を置き換えます。
+pub fn expand_template(template: &str, title: &str, tags: &[String], now: &DateTime<Local>) -> String {
+    let tags_out: Vec<String> = tags.iter().map(|tag| format!("#{}", tag)).collect();
+    let user = env::var("USERNAME").or_else(|_| env::var("USER")).unwrap_or_default();
+    template
+        .replace("{{title}}", title)
+        .replace("{{date}}", &now.format("%Y-%m-%d").to_string())
+        .replace("{{time}}", &now.format("%H:%M").to_string())
+        .replace("{{tags}}", &tags_out.join(" "))
+        .replace("{{user}}", &user)
+}
+
+/// 新しいメモの内容を作ります。
+/// タグが検索できるよう、先頭には必ずタグのヘッダーを付けます。テンプレートの`{{tags}}`は表示用です。
+pub fn create_memo_contents(template: Option<&str>, title: &str, tags: &[String], now: &DateTime<Local>) -> String {
+    let header = memo::create_tags_header(tags);
+    match template {
+        Some(template) => header + &expand_template(template, title, tags, now),
+        None => header,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    #[test]
+    fn create_memo_contents_test() {
+        let now = Local.with_ymd_and_hms(2021, 10, 3, 9, 5, 0).unwrap();
+        let tags = vec!["meeting".to_string(), "work".to_string()];
+        assert_eq!(
+            create_memo_contents(Some("# {{title}} {{date}} {{time}}\n"), "weekly", &tags, &now),
+            " <!---\n tags: #meeting #work \n --->\n# weekly 2021-10-03 09:05\n"
+        );
+        let text = create_memo_contents(Some("# {{title}}\ntags: {{tags}}\n"), "weekly", &tags, &now);
+        assert_eq!(text, " <!---\n tags: #meeting #work \n --->\n# weekly\ntags: #meeting #work\n");
+        assert_eq!(memo::get_tags_from_text(&text), Some(tags));
+    }
+}
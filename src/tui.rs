@@ -51,7 +51,8 @@ pub fn read_line() -> Result<String, Box<dyn Error>> {
     Ok(line)
 }
 
-pub fn launch_tui(lst_memo: &Vec<memo::Memo>) -> Result<(), Box<dyn Error>> {
+/// TUIを起動します。`lst_memo`は全てのメモで、`tags`は最初の絞り込みです。終了するまで戻りません。
+pub fn launch_tui(lst_memo: &Vec<memo::Memo>, tags: &[String]) -> Result<(), Box<dyn Error>> {
 
     let cli: Cli = Cli{tick_rate:250, enhanced_graphics:true};
 
@@ -77,28 +78,38 @@ pub fn launch_tui(lst_memo: &Vec<memo::Memo>) -> Result<(), Box<dyn Error>> {
                     .unwrap_or_else(|| Duration::from_secs(0));
                 if event::poll(timeout).unwrap() {
                     if let CEvent::Key(key) = event::read().unwrap() {
-                        tx.send(Event::Input(key)).unwrap();
+                        // TUIが終了して受信側がなくなったら止める
+                        if tx.send(Event::Input(key)).is_err() {
+                            break;
+                        }
                     }
                 }
                 if last_tick.elapsed() >= tick_rate {
-                    match tx.send(Event::Tick) {
-                        Err(e) => {
-                            panic!("send error:{}", e);
-                        },
-                        _ => {}
+                    if tx.send(Event::Tick).is_err() {
+                        break;
                     }
                     last_tick = Instant::now();
                 }
             }
         });
 
-    let mut app = App::new("Crossterm Demo", lst_memo, cli.enhanced_graphics);
+    let mut app = App::new("Crossterm Demo", lst_memo, tags, cli.enhanced_graphics);
 
     terminal.clear()?;
 
     loop {
         terminal.draw(|f| ui::draw(f, &mut app))?;
         match rx.recv()? {
+            // タグの入力中はキーを入力欄に渡す
+            Event::Input(event) if app.input.is_some() && !event.modifiers.contains(KeyModifiers::CONTROL) => {
+                match event.code {
+                    KeyCode::Char(c) => app.on_input_char(c),
+                    KeyCode::Backspace => app.on_input_backspace(),
+                    KeyCode::Enter => app.on_input_enter(),
+                    KeyCode::Esc => app.on_input_cancel(),
+                    _ => {},
+                }
+            },
             Event::Input(event) => match event.modifiers {
                 KeyModifiers::NONE => {
                     match event.code {
@@ -117,14 +128,7 @@ pub fn launch_tui(lst_memo: &Vec<memo::Memo>) -> Result<(), Box<dyn Error>> {
                         KeyCode::Down => app.on_down(),
                         KeyCode::Enter => app.on_enter_dir(),
                         KeyCode::Esc => {
-                            disable_raw_mode()?;
-                            execute!(
-                                terminal.backend_mut(),
-                                LeaveAlternateScreen,
-                                DisableMouseCapture
-                            )?;
-                            terminal.show_cursor()?;
-                            return Ok(());
+                            app.should_quit = true;
                         }
                         _ => {},
                     }
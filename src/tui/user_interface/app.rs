@@ -1,6 +1,12 @@
 use crate::tui::util::{SinSignal, StatefulList, TabsState};
 use std::process::Command;
+use crate::archive;
+use crate::git;
+use crate::link;
 use crate::memo;
+use crate::task;
+use chrono::Local;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path;
 use std::error::Error;
@@ -57,6 +63,12 @@ pub struct Server<'a> {
     pub status: &'a str,
 }
 
+fn read_setting() -> memo::Setting {
+    let file_setting = fs::File::open("setting.json").expect(&format!("setting.json isn't exist. Please make setting.json at {};", env::current_dir().unwrap().to_str().unwrap()));
+    let reader_setting = BufReader::new(file_setting);
+    serde_json::from_reader(reader_setting).expect("can't read jsonfile correctly. Please ensure json format")
+}
+
 pub fn read_dir(path: &str) -> Result<Vec<path::PathBuf>, Box<dyn Error>> {
     let dir = fs::read_dir(path)?;
     let mut files: Vec<path::PathBuf> = Vec::new();
@@ -77,6 +89,46 @@ fn launch_file(path: &str, app_using_openmemo: &String) -> winrt::Result<()> {
     panic!("not implement");
 }
 
+/// タグツリーペインの1行
+pub struct TagTreeRow {
+    pub depth: usize,
+    pub path: String,
+    pub name: String,
+    pub count: usize,
+    pub has_children: bool,
+    pub expanded: bool,
+}
+
+/// 展開されているノードだけをたどって、タグツリーを表示する行に並べます。
+fn flatten_tag_tree(nodes: &[memo::TagNode], tags_expanded: &HashSet<String>, depth: usize, rows: &mut Vec<TagTreeRow>) {
+    for node in nodes {
+        let expanded = tags_expanded.contains(node.get_path());
+        rows.push(TagTreeRow {
+            depth,
+            path: node.get_path().clone(),
+            name: node.get_name().clone(),
+            count: node.get_count(),
+            has_children: !node.get_children().is_empty(),
+            expanded,
+        });
+        if expanded {
+            flatten_tag_tree(node.get_children(), tags_expanded, depth + 1, rows);
+        }
+    }
+}
+
+/// キー操作の対象になっているペイン
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Focus {
+    TagTree,
+    MemoList,
+    Preview,
+}
+
+/// タブの並び
+pub const TAB_MEMO: usize = 0;
+pub const TAB_AGENDA: usize = 1;
+
 pub struct App<'a> {
     pub title: &'a str,
     pub should_quit: bool,
@@ -87,30 +139,96 @@ pub struct App<'a> {
     pub enhanced_graphics: bool,
     pub folders_index: usize,
     pub path_copied: String,
+    pub memos: Vec<memo::Memo>,
+    pub tag_tree: Vec<memo::TagNode>,
+    pub tags_expanded: HashSet<String>,
+    pub tag_rows: StatefulList<TagTreeRow>,
+    pub focus: Focus,
+    pub tag_selected: Option<String>,
+    pub tags_filter: Vec<String>,
+    pub input: Option<String>,
+    pub agenda: StatefulList<task::Task>,
+    pub backlinks: HashMap<String, Vec<String>>,
+    pub preview_links: Vec<link::WikiLink>,
+    pub preview_backlinks: Vec<String>,
+    /// プレビューで選択中のリンク。`preview_links`の後ろに`preview_backlinks`が続く番号
+    pub preview_link_index: Option<usize>,
+    /// メモ一覧の枠に出す、直前の操作の結果
+    pub message: Option<String>,
 }
 
 impl<'a> App<'a> {
-    pub fn new(title: &'a str, lst_memo: &Vec<memo::Memo>, enhanced_graphics: bool) -> App<'a> {
+    /// `lst_memo`には全てのメモを渡し、`tags`で最初の絞り込みを指定します。
+    pub fn new(title: &'a str, lst_memo: &Vec<memo::Memo>, tags: &[String], enhanced_graphics: bool) -> App<'a> {
 
-        App {
+        let mut app = App {
             title,
             should_quit: false,
-            tabs: TabsState::new(vec!["Tab0", "Tab1", "Tab2"]),
+            tabs: TabsState::new(vec!["Tab0", "Agenda", "Tab2"]),
             show_chart: false,
             progress: 0.0,
-            folders: vec![StatefulList::with_items(lst_memo.clone())] ,
+            folders: vec![StatefulList::new()] ,
             enhanced_graphics,
             folders_index: 0,
             path_copied: "".to_string(),
-        }
+            memos: lst_memo.clone(),
+            tag_tree: Vec::new(),
+            tags_expanded: HashSet::new(),
+            tag_rows: StatefulList::new(),
+            focus: Focus::MemoList,
+            tag_selected: None,
+            tags_filter: tags.to_vec(),
+            input: None,
+            agenda: StatefulList::new(),
+            backlinks: HashMap::new(),
+            preview_links: Vec::new(),
+            preview_backlinks: Vec::new(),
+            preview_link_index: None,
+            message: None,
+        };
+        app.backlinks = link::create_backlink_index(&app.memos);
+        app.tag_tree = memo::create_tag_tree(&app.memos);
+        app.update_tag_rows();
+        app.update_memo_list();
+        app
     }
 
     pub fn on_up(&mut self) {
-        self.folders[self.folders_index].previous();
+        if self.tabs.index == TAB_AGENDA {
+            if !self.agenda.items.is_empty() {
+                self.agenda.previous();
+            }
+        } else if self.focus == Focus::TagTree {
+            if !self.tag_rows.items.is_empty() {
+                self.tag_rows.previous();
+            }
+        } else if self.focus == Focus::Preview {
+            let n = self.preview_links.len() + self.preview_backlinks.len();
+            if n > 0 {
+                self.preview_link_index = Some(self.preview_link_index.map_or(n - 1, |i| (i + n - 1) % n));
+            }
+        } else if !self.folders[self.folders_index].items.is_empty() {
+            self.folders[self.folders_index].previous();
+        }
     }
 
     pub fn on_down(&mut self) {
-        self.folders[self.folders_index].next();
+        if self.tabs.index == TAB_AGENDA {
+            if !self.agenda.items.is_empty() {
+                self.agenda.next();
+            }
+        } else if self.focus == Focus::TagTree {
+            if !self.tag_rows.items.is_empty() {
+                self.tag_rows.next();
+            }
+        } else if self.focus == Focus::Preview {
+            let n = self.preview_links.len() + self.preview_backlinks.len();
+            if n > 0 {
+                self.preview_link_index = Some(self.preview_link_index.map_or(0, |i| (i + 1) % n));
+            }
+        } else if !self.folders[self.folders_index].items.is_empty() {
+            self.folders[self.folders_index].next();
+        }
     }
 
     pub fn on_right(&mut self) {
@@ -122,14 +240,26 @@ impl<'a> App<'a> {
     }
 
     pub fn on_enter_dir(&mut self) {
+        if self.tabs.index != TAB_MEMO {
+            return;
+        }
+        match self.focus {
+            Focus::TagTree => {
+                self.on_select_tag();
+                return;
+            }
+            Focus::Preview => {
+                self.on_follow_link();
+                return;
+            }
+            Focus::MemoList => {}
+        }
         match self.folders[self.folders_index].state.selected() {
             Some(x) => {
                 let path_target = &self.folders[self.folders_index].items[x].get_path();
                 let path_target = path::Path::new(path_target);
                 let path_target = path::PathBuf::from(path_target);
-                let file_setting = fs::File::open("setting.json").expect(&format!("setting.json isn't exist. Please make setting.json at {};", env::current_dir().unwrap().to_str().unwrap()));
-                let reader_setting = BufReader::new(file_setting);
-                let setting: memo::Setting = serde_json::from_reader(reader_setting).expect("can't read jsonfile correctly. Please ensure json format");
+                let setting = read_setting();
                 launch_file(path_target.to_str().unwrap(), setting.get_app_using_openmemo()).unwrap();
             },
             _ => {}
@@ -137,11 +267,209 @@ impl<'a> App<'a> {
     }
 
     pub fn on_focus_left_pain(&mut self) {
-        self.folders_index = 0;
+        self.focus = match self.focus {
+            Focus::Preview => Focus::MemoList,
+            _ => Focus::TagTree,
+        };
     }
 
     pub fn on_focus_right_pain(&mut self) {
-        self.folders_index = 1;
+        self.focus = match self.focus {
+            Focus::TagTree => Focus::MemoList,
+            _ => {
+                self.load_preview_links();
+                Focus::Preview
+            }
+        };
+    }
+
+    pub fn get_selected_memo(&self) -> Option<&memo::Memo> {
+        let folder = &self.folders[self.folders_index];
+        folder.state.selected().and_then(|i| folder.items.get(i))
+    }
+
+    /// プレビュー中のメモの`[[link]]`とバックリンクを読み込み、最初のリンクを選択します。
+    fn load_preview_links(&mut self) {
+        let (links, backlinks) = match self.get_selected_memo() {
+            Some(memo) => (
+                match memo::read_text(path::Path::new(memo.get_path())) {
+                    Ok(text) => link::extract_wiki_links(&text),
+                    Err(_) => Vec::new(),
+                },
+                self.backlinks.get(memo.get_path()).cloned().unwrap_or_default(),
+            ),
+            None => (Vec::new(), Vec::new()),
+        };
+        self.preview_links = links;
+        self.preview_backlinks = backlinks;
+        let n = self.preview_links.len() + self.preview_backlinks.len();
+        self.preview_link_index = if n == 0 { None } else { Some(0) };
+    }
+
+    /// プレビューで選択中の`[[link]]`またはバックリンクのメモに移動します。
+    pub fn on_follow_link(&mut self) {
+        let path = match self.preview_link_index {
+            Some(i) if i < self.preview_links.len() => {
+                match link::resolve_wiki_link(&self.preview_links[i].target, &self.memos) {
+                    Some(memo) => memo.get_path().clone(),
+                    None => return,
+                }
+            }
+            Some(i) => match self.preview_backlinks.get(i - self.preview_links.len()) {
+                Some(path) => path.clone(),
+                None => return,
+            },
+            None => return,
+        };
+        self.select_memo(&path);
+    }
+
+    /// メモ一覧でそのメモを選択します。一覧に無い場合は絞り込みを解除します。
+    fn select_memo(&mut self, path: &str) {
+        let find = |app: &App| app.folders[app.folders_index].items.iter().position(|memo| memo.get_path() == path);
+        let index = match find(self) {
+            Some(index) => Some(index),
+            None => {
+                self.tags_filter.clear();
+                self.tag_selected = None;
+                self.update_memo_list();
+                find(self)
+            }
+        };
+        self.folders[self.folders_index].state.select(index);
+        self.load_preview_links();
+    }
+
+    /// タグツリーの表示行を作り直します。選択位置はなるべく同じタグに合わせます。
+    fn update_tag_rows(&mut self) {
+        let path_selected = self.tag_rows.state.selected().map(|i| self.tag_rows.items[i].path.clone());
+        let mut rows: Vec<TagTreeRow> = Vec::new();
+        flatten_tag_tree(&self.tag_tree, &self.tags_expanded, 0, &mut rows);
+        let selected = match path_selected {
+            Some(path) => rows.iter().position(|row| row.path == path),
+            None => None,
+        };
+        self.tag_rows = StatefulList::with_items(rows);
+        self.tag_rows.state.select(selected);
+    }
+
+    /// 選択中のタグノードを展開/折りたたみます。
+    pub fn on_toggle_tag(&mut self) {
+        if let Some(i) = self.tag_rows.state.selected() {
+            let row = &self.tag_rows.items[i];
+            if !row.has_children {
+                return;
+            }
+            let path = row.path.clone();
+            if !self.tags_expanded.remove(&path) {
+                self.tags_expanded.insert(path);
+            }
+            self.update_tag_rows();
+        }
+    }
+
+    /// 選択中のタグ(子孫のタグを含む)でメモ一覧を絞り込みます。同じタグをもう一度選ぶと解除します。
+    pub fn on_select_tag(&mut self) {
+        if let Some(i) = self.tag_rows.state.selected() {
+            let path = self.tag_rows.items[i].path.clone();
+            self.tag_selected = if self.tag_selected.as_ref() == Some(&path) {
+                None
+            } else {
+                Some(path)
+            };
+            self.update_memo_list();
+        }
+    }
+
+    /// タグの絞り込みとタグツリーでの選択から、メモ一覧を作り直します。
+    fn update_memo_list(&mut self) {
+        let mut lst_memo = if self.tags_filter.is_empty() {
+            self.memos.clone()
+        } else {
+            memo::filter_memo_by_tags(&self.memos, &self.tags_filter)
+        };
+        if let Some(tag) = &self.tag_selected {
+            lst_memo.retain(|memo| memo::is_include_these_tags(&vec![tag.clone()], memo.get_tags()));
+        }
+        self.update_agenda(&lst_memo);
+        self.folders[self.folders_index] = StatefulList::with_items(lst_memo);
+    }
+
+    /// 絞り込まれたメモから、期日のある未完了のタスクを期日順に集めます。
+    fn update_agenda(&mut self, lst_memo: &[memo::Memo]) {
+        let mut tasks: Vec<task::Task> = task::create_task_list(lst_memo)
+            .into_iter()
+            .filter(|task| !task.is_done() && task.get_due().is_some())
+            .collect();
+        task::sort_by_due(&mut tasks);
+        self.agenda = StatefulList::with_items(tasks);
+    }
+
+    /// 選択中のメモをアーカイブのディレクトリへ移動します。
+    pub fn on_archive_memo(&mut self) {
+        self.remove_selected_memo("archived", |path, setting| archive::archive_memo(path, setting.get_archive_path()));
+    }
+
+    /// 選択中のメモをゴミ箱へ移動します。
+    pub fn on_trash_memo(&mut self) {
+        self.remove_selected_memo("moved to trash", |path, _| archive::trash_memo(path, Local::now().naive_local()));
+    }
+
+    /// 選択中のメモを`action`で移動し、一覧から除きます。
+    fn remove_selected_memo<F>(&mut self, verb: &str, action: F)
+    where
+        F: FnOnce(&path::Path, &memo::Setting) -> anyhow::Result<path::PathBuf>,
+    {
+        if self.tabs.index != TAB_MEMO || self.focus != Focus::MemoList {
+            return;
+        }
+        let path_memo = match self.get_selected_memo() {
+            Some(memo) => memo.get_path().clone(),
+            None => return,
+        };
+        let setting = read_setting();
+        let path_memo = path::Path::new(&path_memo);
+        match action(path_memo, &setting) {
+            Ok(dest) => {
+                git::auto_commit(&setting, path_memo, &format!("{} {}", verb, path_memo.display()));
+                self.message = Some(format!("{} {}", verb, dest.display()));
+                self.memos.retain(|memo| path::Path::new(memo.get_path()) != path_memo);
+                self.backlinks = link::create_backlink_index(&self.memos);
+                self.tag_tree = memo::create_tag_tree(&self.memos);
+                self.update_tag_rows();
+                self.update_memo_list();
+            }
+            Err(e) => self.message = Some(format!("{:#}", e)),
+        }
+    }
+
+    /// タグの絞り込みを編集する入力欄を開きます。
+    pub fn on_edit_tags(&mut self) {
+        self.input = Some(self.tags_filter.join(" "));
+    }
+
+    pub fn on_input_char(&mut self, c: char) {
+        if let Some(input) = self.input.as_mut() {
+            input.push(c);
+        }
+    }
+
+    pub fn on_input_backspace(&mut self) {
+        if let Some(input) = self.input.as_mut() {
+            input.pop();
+        }
+    }
+
+    pub fn on_input_cancel(&mut self) {
+        self.input = None;
+    }
+
+    /// 入力されたタグ(空白区切り)で絞り込みます。空の場合は全てのメモを表示します。
+    pub fn on_input_enter(&mut self) {
+        if let Some(input) = self.input.take() {
+            self.tags_filter = input.split_whitespace().map(|x| x.to_string()).collect();
+            self.update_memo_list();
+        }
     }
 
     pub fn search_string_in_this_path(&mut self, search: &str) {
@@ -167,21 +495,20 @@ impl<'a> App<'a> {
                 self.should_quit = true;
             }
             't' => {
-                self.show_chart = !self.show_chart;
+                self.on_edit_tags();
             }
             'j' => { self.on_down(); }
             'k' => { self.on_up(); }
             'c' => { self.on_enter_dir(); }
             'l' => { self.on_focus_right_pain(); }
             'h' => { self.on_focus_left_pain(); }
+            ' ' => { self.on_toggle_tag(); }
+            'a' => { self.on_archive_memo(); }
+            'x' => { self.on_trash_memo(); }
             _ => {}
         }
     }
 
-    pub fn add_task(&mut self) {
-
-    }
-
     pub fn on_tick(&mut self) {
         // Update progress
         self.progress += 0.001;
@@ -1,3 +1,3 @@
 mod app;
 pub mod ui;
-pub use app::App;
+pub use app::{App, Focus, TAB_AGENDA, TAB_MEMO};
@@ -1,4 +1,8 @@
-use crate::tui::user_interface::App;
+use crate::markup::{ColumnAlign, Item, Parser, Table};
+use crate::link;
+use crate::task;
+use crate::tui::user_interface::{App, Focus, TAB_AGENDA, TAB_MEMO};
+use chrono::Local;
 use std::fs::{read_to_string, read};
 use tui::{
     backend::Backend,
@@ -11,6 +15,7 @@ use tui::{
     },
     Frame,
 };
+use unicode_width::UnicodeWidthStr;
 
 pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
     let chunks = Layout::default()
@@ -28,7 +33,8 @@ pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
         .select(app.tabs.index);
     f.render_widget(tabs, chunks[0]);
     match app.tabs.index {
-        0 => draw_first_tab(f, app, chunks[1]),
+        TAB_MEMO => draw_first_tab(f, app, chunks[1]),
+        TAB_AGENDA => draw_agenda_tab(f, app, chunks[1]),
         _ => {}
     };
 }
@@ -47,7 +53,34 @@ where
         )
         .split(area);
     draw_charts(f, app, chunks[0]);
-    draw_text(f, chunks[1]);
+    match &app.input {
+        Some(input) => draw_input(f, input, chunks[1]),
+        None => draw_text(f, chunks[1]),
+    }
+}
+
+fn draw_input<B>(f: &mut Frame<B>, input: &str, area: Rect)
+where
+    B: Backend,
+{
+    let text = vec![
+        Spans::from(vec![
+            Span::raw(input.to_string()),
+            Span::styled("_", Style::default().add_modifier(Modifier::SLOW_BLINK)),
+        ]),
+        Spans::from(Span::styled(
+            "Enter: 絞り込み  Esc: キャンセル  (空白区切りで複数タグ、空で全件)",
+            Style::default().fg(Color::DarkGray),
+        )),
+    ];
+    let block = Block::default().borders(Borders::ALL).title(Span::styled(
+        "Tags",
+        Style::default()
+            .fg(Color::Yellow)
+            .add_modifier(Modifier::BOLD),
+    ));
+    let paragraph = Paragraph::new(text).block(block).wrap(Wrap { trim: true });
+    f.render_widget(paragraph, area);
 }
 
 fn draw_charts<B>(f: &mut Frame<B>, app: &mut App, area: Rect)
@@ -65,10 +98,13 @@ where
         .split(area);
     {
         let chunks = Layout::default()
-            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .constraints([Constraint::Percentage(20), Constraint::Percentage(40), Constraint::Percentage(40)].as_ref())
             .direction(Direction::Horizontal)
             .split(chunks[0]);
 
+        draw_tag_tree(f, app, chunks[0]);
+        let chunks = &chunks[1..];
+
         // Draw tasks
         let tasks: Vec<ListItem> = app
             .folders[0]
@@ -77,7 +113,10 @@ where
             .map(|i| ListItem::new(vec![Spans::from(Span::raw(format!("{}", i)))]))
             .collect();
         let tasks = List::new(tasks)
-            .block(Block::default().borders(Borders::ALL).title("Task"))
+            .block(Block::default().borders(Borders::ALL).title(match &app.message {
+                Some(message) => format!("Task - tags: {} - {}", app.tags_filter.join(" "), message),
+                None => format!("Task - tags: {}", app.tags_filter.join(" ")),
+            }))
             //.highlight_style(Style::default().add_modifier(Modifier::BOLD))
             .highlight_style(Style::default().fg(Color::Red))
             .highlight_symbol("> ");
@@ -95,21 +134,231 @@ where
                         res.into_owned()
                     }
                 };
-                let text: Vec<Spans> = contents.lines().into_iter().map(|line| Spans::from(line)).collect();
-                let block = Block::default().borders(Borders::ALL).title(Span::styled(
+                let link_selected = app.preview_link_index.filter(|_| app.focus == Focus::Preview);
+                let text = create_preview_text(&contents, link_selected);
+                let border_style = if app.focus == Focus::Preview {
+                    Style::default().fg(Color::Yellow)
+                } else {
+                    Style::default()
+                };
+                let block = Block::default().borders(Borders::ALL).border_style(border_style).title(Span::styled(
                     "Preview",
                     Style::default()
                         .fg(Color::Yellow)
                         .add_modifier(Modifier::BOLD),
                 ));
                 let paragraph = Paragraph::new(text).block(block).wrap(Wrap { trim: true });
-                f.render_widget(paragraph, chunks[1]);
+
+                // プレビューの下にバックリンクを出す
+                let backlinks = app.backlinks.get(path).cloned().unwrap_or_default();
+                let chunks = Layout::default()
+                    .constraints([Constraint::Min(0), Constraint::Length(backlinks.len().min(6) as u16 + 2)].as_ref())
+                    .split(chunks[1]);
+                f.render_widget(paragraph, chunks[0]);
+
+                // プレビューで選択できるバックリンクは、フォーカスしたときに読み込んだもの
+                let n_link = if app.focus == Focus::Preview { app.preview_links.len() } else { 0 };
+                let items: Vec<ListItem> = backlinks
+                    .iter()
+                    .enumerate()
+                    .map(|(i, source)| {
+                        let style = if link_selected == Some(n_link + i) {
+                            Style::default().add_modifier(Modifier::REVERSED)
+                        } else {
+                            Style::default()
+                        };
+                        ListItem::new(Span::styled(format!("<- {}", source), style))
+                    })
+                    .collect();
+                let list = List::new(items).block(Block::default().borders(Borders::ALL).title("Backlinks"));
+                f.render_widget(list, chunks[1]);
             }
             _ => {}
         }
     }
 }
 
+/// プレビューの本文を作ります。`[[link]]`を強調し、`link_selected`番目のリンクを反転表示します。
+/// リンクの数え方は`link::extract_wiki_links`と同じで、コードブロックの中は数えません。表は罫線で囲みます。
+fn create_preview_text(contents: &str, link_selected: Option<usize>) -> Vec<Spans<'_>> {
+    let mut n_link = 0;
+    let mut is_code_block = false;
+    let mut text: Vec<Spans> = Vec::new();
+    let mut lines = contents.split_inclusive('\n');
+    let mut rest = contents;
+    while let Some(line_raw) = lines.next() {
+        let line = line_raw.trim_end_matches(&['\r', '\n'][..]);
+        let rest_line = rest;
+        rest = &rest[line_raw.len()..];
+        if line.trim_start().starts_with("```") {
+            is_code_block = !is_code_block;
+            text.push(Spans::from(line));
+            continue;
+        }
+        if is_code_block {
+            text.push(Spans::from(line));
+            continue;
+        }
+        if let Some(Item::Table(table)) = Parser::new(rest_line).next() {
+            // 見出しと区切りの行の後に本文の行が続く
+            for line_raw in lines.by_ref().take(1 + table.rows().len()) {
+                rest = &rest[line_raw.len()..];
+            }
+            text.extend(create_table_text(&table, &mut n_link, link_selected));
+            continue;
+        }
+        let mut spans: Vec<Span> = Vec::new();
+        push_spans_with_links(&mut spans, line, &mut n_link, link_selected, Style::default());
+        text.push(Spans::from(spans));
+    }
+    text
+}
+
+/// `text`を`[[link]]`を強調したSpanにして追加します。
+fn push_spans_with_links<'a>(
+    spans: &mut Vec<Span<'a>>,
+    text: &'a str,
+    n_link: &mut usize,
+    link_selected: Option<usize>,
+    style: Style,
+) {
+    let style_link = style.fg(Color::Cyan).add_modifier(Modifier::UNDERLINED);
+    let mut rest = text;
+    while let Some(start) = rest.find("[[") {
+        match link::parse_wiki_link(&rest[start..]) {
+            Some((_, _, len)) => {
+                let style_link = if link_selected == Some(*n_link) {
+                    style_link.add_modifier(Modifier::REVERSED)
+                } else {
+                    style_link
+                };
+                spans.push(Span::styled(&rest[..start], style));
+                spans.push(Span::styled(&rest[start..start + len], style_link));
+                rest = &rest[start + len..];
+                *n_link += 1;
+            }
+            None => {
+                spans.push(Span::styled(&rest[..start + 2], style));
+                rest = &rest[start + 2..];
+            }
+        }
+    }
+    spans.push(Span::styled(rest, style));
+}
+
+/// 表を罫線で囲んだ行にします。列の幅は全角文字を2として数えます。
+fn create_table_text<'a>(table: &Table<'a>, n_link: &mut usize, link_selected: Option<usize>) -> Vec<Spans<'a>> {
+    let header = table.header();
+    let rows = table.rows();
+    let alignments = table.alignments();
+    let widths: Vec<usize> = (0..header.len())
+        .map(|i| std::iter::once(&header).chain(rows.iter()).map(|row| row[i].width()).max().unwrap_or(0))
+        .collect();
+    let rule = |left: &str, middle: &str, right: &str| -> Spans<'a> {
+        let cells: Vec<String> = widths.iter().map(|width| "─".repeat(width + 2)).collect();
+        Spans::from(format!("{}{}{}", left, cells.join(middle), right))
+    };
+    let mut row_text = |cells: &[&'a str], style: Style| -> Spans<'a> {
+        let mut spans: Vec<Span> = vec![Span::raw("│")];
+        for ((cell, width), align) in cells.iter().zip(widths.iter()).zip(alignments.iter()) {
+            let padding = width - cell.width();
+            let (left, right) = match align {
+                ColumnAlign::Right => (padding, 0),
+                ColumnAlign::Center => (padding / 2, padding - padding / 2),
+                _ => (0, padding),
+            };
+            spans.push(Span::raw(" ".repeat(left + 1)));
+            push_spans_with_links(&mut spans, cell, n_link, link_selected, style);
+            spans.push(Span::raw(format!("{}│", " ".repeat(right + 1))));
+        }
+        Spans::from(spans)
+    };
+    let mut text = vec![rule("┌", "┬", "┐")];
+    text.push(row_text(&header, Style::default().add_modifier(Modifier::BOLD)));
+    text.push(rule("├", "┼", "┤"));
+    for row in rows.iter() {
+        text.push(row_text(row, Style::default()));
+    }
+    text.push(rule("└", "┴", "┘"));
+    text
+}
+
+fn draw_agenda_tab<B>(f: &mut Frame<B>, app: &mut App, area: Rect)
+where
+    B: Backend,
+{
+    let today = Local::now().date_naive();
+    let tasks: Vec<ListItem> = app
+        .agenda
+        .items
+        .iter()
+        .map(|task| {
+            let due = task.get_due().unwrap();
+            let style = if due < today {
+                Style::default().fg(Color::Red)
+            } else if due == today {
+                Style::default().fg(Color::Yellow)
+            } else {
+                Style::default()
+            };
+            let style_text = if task.get_priority() == Some(task::Priority::High) {
+                Style::default().add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            ListItem::new(Spans::from(vec![
+                Span::styled(due.format("%Y-%m-%d").to_string(), style),
+                Span::styled(format!(" {}  ", task.get_text()), style_text),
+                Span::styled(task.get_path().clone(), Style::default().fg(Color::DarkGray)),
+            ]))
+        })
+        .collect();
+    let tasks = List::new(tasks)
+        .block(Block::default().borders(Borders::ALL).title("Agenda"))
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+    f.render_stateful_widget(tasks, area, &mut app.agenda.state);
+}
+
+fn draw_tag_tree<B>(f: &mut Frame<B>, app: &mut App, area: Rect)
+where
+    B: Backend,
+{
+    let tags: Vec<ListItem> = app
+        .tag_rows
+        .items
+        .iter()
+        .map(|row| {
+            let marker = match (row.has_children, row.expanded) {
+                (false, _) => "  ",
+                (true, false) => "▸ ",
+                (true, true) => "▾ ",
+            };
+            let style = if app.tag_selected.as_ref() == Some(&row.path) {
+                Style::default().fg(Color::Yellow)
+            } else {
+                Style::default()
+            };
+            ListItem::new(Spans::from(vec![
+                Span::raw("  ".repeat(row.depth)),
+                Span::raw(marker),
+                Span::styled(row.name.clone(), style),
+                Span::styled(format!(" ({})", row.count), Style::default().fg(Color::DarkGray)),
+            ]))
+        })
+        .collect();
+    let border_style = if app.focus == Focus::TagTree {
+        Style::default().fg(Color::Yellow)
+    } else {
+        Style::default()
+    };
+    let tags = List::new(tags)
+        .block(Block::default().borders(Borders::ALL).border_style(border_style).title("Tags"))
+        .highlight_style(Style::default().fg(Color::Red))
+        .highlight_symbol("> ");
+    f.render_stateful_widget(tags, area, &mut app.tag_rows.state);
+}
+
 fn draw_text<B>(f: &mut Frame<B>, area: Rect)
 where
     B: Backend,
@@ -130,12 +379,30 @@ where
             Span::raw("\": "),
             Span::from("next"),
         ]),
+        Spans::from(vec![
+            Span::raw("  key\""),
+            Span::styled("t", Style::default().add_modifier(Modifier::BOLD).fg(Color::Yellow)),
+            Span::raw("\": "),
+            Span::from("タグで絞り込み"),
+        ]),
+        Spans::from(vec![
+            Span::raw("  key\""),
+            Span::styled("a/x", Style::default().add_modifier(Modifier::BOLD).fg(Color::Yellow)),
+            Span::raw("\": "),
+            Span::from("メモをアーカイブ / ゴミ箱へ移動"),
+        ]),
         Spans::from(vec![
             Span::raw("  key\""),
             Span::styled("k", Style::default().add_modifier(Modifier::BOLD).fg(Color::Green)),
             Span::raw("\": "),
             Span::from("pre"),
         ]),
+        Spans::from(vec![
+            Span::raw("  key\""),
+            Span::styled("h/l", Style::default().add_modifier(Modifier::BOLD).fg(Color::Yellow)),
+            Span::raw("\": "),
+            Span::from("ペインを移動 (タグツリーで space: 展開, Enter: 絞り込み / プレビューで j/k: リンク選択, Enter: リンク先へ)"),
+        ]),
         Spans::from(
             "One more thing is that it should display unicode characters: 10€"
         ),